@@ -4,10 +4,15 @@ use std::{
 };
 
 use base64::prelude::*;
+use directories::ProjectDirs;
 use image_dds::{ImageFormat, Mipmaps, Quality, image::codecs::png::PngEncoder};
 use rayon::prelude::*;
 use rfd::FileDialog;
-use ultimate_tex_lib::{ImageFile, NutexbFile};
+use ultimate_tex_lib::{
+    BatchFile, BatchJob, BatchOutput, BatchProgress, ColorSpace, Error as LibError, ImageExport,
+    ImageFile, ImageFileType, OutputOverrides, OutputSettings, Presets, Slices, TextureInfo,
+    TextureProperties, ValidationIssue, find_nutexb_files, group_layer_files, validate,
+};
 
 // TODO: Add proper logging using events?
 #[derive(Clone, Default)]
@@ -16,32 +21,37 @@ pub struct App {
     pub png_thumbnails: Vec<String>,
 }
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct AppSettings {
     pub output_folder: Option<PathBuf>,
     pub save_in_same_folder: bool,
-    pub overrides: FileSettingsOverrides,
+    /// How to save image file types like PNG for layered, 3D, or packed textures.
+    pub image_export: ImageExport,
+    pub report_quality: bool,
+    pub save_heatmaps: bool,
+    pub overrides: OutputOverrides,
     pub file_settings: Vec<ImageFileSettings>,
 }
 
-#[derive(Clone)]
-pub struct FileSettingsOverrides {
-    pub output_file_type: Option<ImageFileType>,
-    pub output_format: Option<ImageFormat>,
-    pub mipmaps: Option<Mipmaps>,
-    pub output_quality: Option<Quality>,
-}
-
 #[derive(Clone)]
 pub struct ImageFileSettings {
     pub name: String,
     pub path: PathBuf,
+    /// The separate image for each layer if this file combines multiple images.
+    pub layer_paths: Vec<PathBuf>,
     pub format: ImageFormat,
-    pub dimensions: (u32, u32, u32),
+    /// The bits per channel for image files like 16-bit PNG.
+    pub bits_per_channel: Option<u32>,
+    /// The width, height, depth, and layer count.
+    pub dimensions: (u32, u32, u32, u32),
     pub output_file_type: ImageFileType,
     pub output_format: ImageFormat,
     pub output_quality: Quality,
     pub output_mipmaps: Mipmaps,
+    /// The name stored in nutexb and bntx files.
+    pub internal_name: String,
+    /// The color space for the file's role based on the presets.
+    pub expected_color_space: Option<ColorSpace>,
 }
 
 impl App {
@@ -55,208 +65,356 @@ impl App {
         self.png_thumbnails = Vec::new();
     }
 
-    pub fn convert_and_export_files(&self) -> Result<Vec<String>, Box<dyn Error>> {
-        // TODO: Log an error if creating the output directory fails.
-        if let Some(output_folder) = &self.settings.output_folder {
-            std::fs::create_dir_all(output_folder)?;
-        }
+    pub fn convert_and_export_files(&self, progress: &BatchProgress) -> Vec<String> {
+        let Some(job) = self.settings.batch_job() else {
+            return vec!["No output folder selected".to_string()];
+        };
 
-        // TODO: report progress?
+        let results = job.run_with_progress(progress);
+
+        // Collect error messages to display to the user.
+        // Cancelled files are summarized in the first message instead.
         let mut messages: Vec<_> = self
             .settings
             .file_settings
-            .par_iter()
-            .filter_map(|settings| {
-                let output = if self.settings.save_in_same_folder {
-                    settings.path.parent()
-                } else {
-                    self.settings.output_folder.as_deref()
-                };
-
-                output.and_then(|output| {
-                    // Collect error messages to display to the user.
-                    let file = ImageFile::from_file(&settings.path).ok()?;
-                    match convert_and_save_file(output, settings, &file, &self.settings.overrides) {
-                        Ok(_) => None,
-                        Err(e) => Some(format!("Error converting {}: {e}", settings.name)),
-                    }
-                })
+            .iter()
+            .zip(&results)
+            .filter_map(|(settings, result)| match &result.result {
+                Err(LibError::Cancelled) | Ok(_) => None,
+                Err(e) => Some(format!("Error converting {}: {e}", settings.name)),
             })
             .collect();
 
         // Always show basic results for the first message.
-        messages.insert(
-            0,
+        let converted = results.iter().filter(|r| r.result.is_ok()).count();
+        let summary = if progress.is_cancelled() {
             format!(
-                "Successfully converted {} of {} file(s)",
-                self.settings.file_settings.len() - messages.len(),
-                self.settings.file_settings.len(),
-            ),
-        );
+                "Cancelled after converting {converted} of {} file(s)",
+                results.len()
+            )
+        } else {
+            format!(
+                "Successfully converted {converted} of {} file(s)",
+                results.len()
+            )
+        };
+        messages.insert(0, summary);
+
+        // Show the quality lost from compression if enabled.
+        messages.extend(self.settings.file_settings.iter().zip(&results).filter_map(
+            |(settings, result)| {
+                let metrics = result.result.as_ref().ok()?.metrics?;
+                Some(format!("{}: {metrics}", settings.name))
+            },
+        ));
+
+        // Exported files may still have problems in game.
+        messages.extend(self.settings.file_settings.iter().flat_map(|settings| {
+            settings
+                .validate(&self.settings.overrides)
+                .into_iter()
+                .map(|issue| format!("Warning: {}: {issue}", settings.name))
+        }));
+
+        messages
+    }
+}
+
+impl AppSettings {
+    fn batch_job(&self) -> Option<BatchJob> {
+        let output = if self.save_in_same_folder {
+            BatchOutput::SameFolder
+        } else {
+            BatchOutput::Folder(self.output_folder.clone()?)
+        };
+
+        Some(BatchJob {
+            files: self
+                .file_settings
+                .iter()
+                .map(ImageFileSettings::batch_file)
+                .collect(),
+            overrides: self.overrides,
+            output,
+            image_export: self.image_export,
+            report_quality: self.report_quality,
+            save_heatmaps: self.save_heatmaps,
+        })
+    }
+}
 
-        Ok(messages)
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            output_folder: None,
+            save_in_same_folder: false,
+            image_export: ImageExport::Base,
+            report_quality: false,
+            save_heatmaps: false,
+            // Default to a custom output format to encourage lossless conversions.
+            overrides: OutputOverrides {
+                file_type: Some(ImageFileType::Png),
+                format: None,
+                quality: Some(Quality::Fast),
+                mipmaps: Some(Mipmaps::GeneratedAutomatic),
+            },
+            file_settings: Vec::new(),
+        }
     }
 }
 
-pub fn pick_files() -> Option<(Vec<String>, Vec<ImageFileSettings>)> {
+/// The settings for newly loaded files and errors for files that failed to load.
+#[derive(Default)]
+pub struct LoadedFiles {
+    pub settings: Vec<ImageFileSettings>,
+    pub errors: Vec<String>,
+}
+
+pub fn pick_files() -> Option<LoadedFiles> {
     // Don't modify app directly to make it easy to run in a background thread.
     if let Some(files) = FileDialog::new()
         .add_filter(
             "image files",
-            &["png", "tiff", "nutexb", "bntx", "jpeg", "jpg", "dds"],
+            &[
+                "png", "tiff", "nutexb", "bntx", "jpeg", "jpg", "dds", "exr", "hdr",
+            ],
         )
         .pick_files()
     {
-        let (new_thumbnails, new_settings) = load_files(files);
-        Some((new_thumbnails, new_settings))
+        Some(load_files(files))
     } else {
         None
     }
 }
 
-pub fn load_files(files: Vec<PathBuf>) -> (Vec<String>, Vec<ImageFileSettings>) {
+pub fn load_files(files: Vec<PathBuf>) -> LoadedFiles {
     let start = std::time::Instant::now();
 
+    // Combine separate images for cube map faces or array layers.
+    let file_count = files.len();
+    let groups = group_layer_files(files);
+
+    let presets = load_presets();
+
     // Only the expensive file reading benefits from parallelism.
-    // TODO: Store or log errors?
-    let new_files: Vec<_> = files
-        .par_iter()
-        .filter_map(|file| Some((file, ImageFile::from_file(file).ok()?)))
-        .collect();
-    let new_thumbnails: Vec<_> = new_files
-        .par_iter()
-        .map(|(_, image)| encode_png_base64(image))
+    let results: Vec<_> = groups
+        .into_par_iter()
+        .map(|(path, layer_paths)| load_group(path, layer_paths, &presets))
         .collect();
 
-    let new_settings = new_files
-        .into_iter()
-        .map(|(file, image)| ImageFileSettings::from_image(file.clone(), &image))
-        .collect();
+    let mut new_settings = Vec::new();
+    let mut errors = Vec::new();
+    for (settings, group_errors) in results {
+        new_settings.extend(settings);
+        errors.extend(group_errors);
+    }
 
-    println!("Loaded {} files in {:?}", files.len(), start.elapsed());
-    (new_thumbnails, new_settings)
+    println!("Loaded {} files in {:?}", file_count, start.elapsed());
+    LoadedFiles {
+        settings: new_settings,
+        errors,
+    }
 }
 
-fn encode_png_base64(f: &ImageFile) -> String {
-    // Convert to an html compatible format.
-    let mut image = f.to_image().unwrap();
-    // Disable alpha for better display of PRM and NOR.
-    image.pixels_mut().for_each(|p| p[3] = 255u8);
-
-    let mut png_bytes = Vec::new();
-    let encoder = PngEncoder::new(&mut png_bytes);
-    image.write_with_encoder(encoder).unwrap();
-
-    "data:image/png;base64,".to_string() + &BASE64_STANDARD.encode(png_bytes)
+/// Decode the base mipmap of each file for the file list.
+/// This is slower than loading the settings, so thumbnails are loaded separately.
+pub fn load_thumbnails(settings: &[ImageFileSettings]) -> Vec<String> {
+    // Files with invalid image data still show up without a thumbnail.
+    settings
+        .par_iter()
+        .map(|settings| {
+            settings
+                .batch_file()
+                .load()
+                .ok()
+                .and_then(|image| encode_png_base64(&image).ok())
+                .unwrap_or_default()
+        })
+        .collect()
 }
 
-impl Default for FileSettingsOverrides {
-    fn default() -> Self {
-        // Default to a custom output format to encourage lossless conversions.
-        Self {
-            output_file_type: Some(ImageFileType::Png),
-            output_format: None,
-            mipmaps: Some(Mipmaps::GeneratedAutomatic),
-            output_quality: Some(Quality::Fast),
+fn load_group(
+    path: PathBuf,
+    layer_paths: Vec<PathBuf>,
+    presets: &Presets,
+) -> (Vec<ImageFileSettings>, Vec<String>) {
+    match load_texture_info(&path, &layer_paths) {
+        Ok(info) => (
+            vec![ImageFileSettings::from_info(
+                path,
+                layer_paths,
+                &info,
+                presets,
+            )],
+            Vec::new(),
+        ),
+        Err(e) if layer_paths.is_empty() => {
+            (Vec::new(), vec![format!("Error loading {path:?}: {e}")])
+        }
+        Err(e) => {
+            // Add the layers as separate files if they can't be combined.
+            let mut errors = vec![format!("Error combining layers for {path:?}: {e}")];
+            let mut settings = Vec::new();
+            for layer_path in layer_paths {
+                match TextureInfo::from_file(&layer_path) {
+                    Ok(info) => settings.push(ImageFileSettings::from_info(
+                        layer_path,
+                        Vec::new(),
+                        &info,
+                        presets,
+                    )),
+                    Err(e) => errors.push(format!("Error loading {layer_path:?}: {e}")),
+                }
+            }
+            (settings, errors)
         }
     }
 }
 
-#[derive(PartialEq, Clone, Copy, strum::Display, strum::EnumIter, strum::EnumString)]
-pub enum ImageFileType {
-    Dds,
-    Png,
-    Tiff,
-    Nutexb,
-    Bntx,
+/// The path for user defined rules for choosing output settings from file names.
+pub fn presets_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "ultimate_tex").map(|dirs| dirs.config_dir().join("presets.json"))
 }
 
-impl Default for ImageFileType {
-    fn default() -> Self {
-        Self::Png
+fn load_presets() -> Presets {
+    match presets_path() {
+        Some(path) if path.exists() => Presets::from_file(&path).unwrap_or_else(|e| {
+            println!("Error loading presets from {path:?}: {e}");
+            Presets::default()
+        }),
+        _ => Presets::default(),
     }
 }
 
-impl ImageFileType {
-    fn extension(&self) -> &'static str {
-        match self {
-            ImageFileType::Dds => "dds",
-            ImageFileType::Png => "png",
-            ImageFileType::Tiff => "tiff",
-            ImageFileType::Nutexb => "nutexb",
-            ImageFileType::Bntx => "bntx",
+/// Open the presets file and create a file with the default presets if needed.
+pub fn open_presets_file() -> Result<(), Box<dyn Error>> {
+    let path = presets_path().ok_or("Failed to find the config directory")?;
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
+        Presets::default().save(&path)?;
     }
+    open::that(path)?;
+    Ok(())
+}
+
+fn load_texture_info(path: &Path, layer_paths: &[PathBuf]) -> Result<TextureInfo, LibError> {
+    if layer_paths.is_empty() {
+        // Avoid decoding the image data just to read the format and dimensions.
+        TextureInfo::from_file(path)
+    } else {
+        TextureInfo::from_slice_files(layer_paths, Slices::Layers)
+    }
+}
+
+fn encode_png_base64(f: &ImageFile) -> Result<String, Box<dyn Error>> {
+    // Convert to an html compatible format.
+    let mut image = f.to_image()?;
+    // Disable alpha for better display of PRM and NOR.
+    image.pixels_mut().for_each(|p| p[3] = 255u8);
+
+    let mut png_bytes = Vec::new();
+    let encoder = PngEncoder::new(&mut png_bytes);
+    image.write_with_encoder(encoder)?;
+
+    Ok("data:image/png;base64,".to_string() + &BASE64_STANDARD.encode(png_bytes))
 }
 
 impl ImageFileSettings {
-    fn from_image(path: PathBuf, image: &ImageFile) -> Self {
+    fn from_info(
+        path: PathBuf,
+        layer_paths: Vec<PathBuf>,
+        info: &TextureInfo,
+        presets: &Presets,
+    ) -> Self {
         // Default to the input format to encourage lossless conversions.
-        let format = image.image_format();
+        let output = presets.apply(
+            &path,
+            OutputSettings {
+                file_type: ImageFileType::Nutexb,
+                format: info.default_output_format(),
+                quality: Quality::Fast,
+                mipmaps: Mipmaps::GeneratedAutomatic,
+            },
+        );
+        let internal_name = info.default_internal_name(&path);
+        let expected_color_space = presets.find_rule(&path).and_then(|r| r.color_space);
         ImageFileSettings {
             name: path.file_name().unwrap().to_string_lossy().to_string(),
             path,
-            format,
-            dimensions: image.dimensions(),
-            output_file_type: ImageFileType::Nutexb,
-            output_format: format,
-            output_quality: Quality::Fast,
-            output_mipmaps: Mipmaps::GeneratedAutomatic,
+            layer_paths,
+            format: info.format,
+            bits_per_channel: info.bits_per_channel,
+            dimensions: (info.width, info.height, info.depth, info.layer_count),
+            output_file_type: output.file_type,
+            output_format: output.format,
+            output_quality: output.quality,
+            output_mipmaps: output.mipmaps,
+            internal_name,
+            expected_color_space,
         }
     }
 
-    fn file_name_no_extension(&self) -> String {
-        // TODO: Avoid unwrap.
-        self.path
-            .with_extension("")
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string()
+    fn output_settings(&self) -> OutputSettings {
+        OutputSettings {
+            file_type: self.output_file_type,
+            format: self.output_format,
+            quality: self.output_quality,
+            mipmaps: self.output_mipmaps,
+        }
     }
-}
 
-pub fn optimize_nutexb_files() {
-    if let Some(folder) = FileDialog::new()
-        .set_title("Select Nutexb Root Folder")
-        .pick_folder()
-    {
-        for entry in globwalk::GlobWalkerBuilder::from_patterns(folder, &["*.{nutexb}"])
-            .build()
-            .unwrap()
-            .filter_map(Result::ok)
-        {
-            if let Ok(mut nutexb) = NutexbFile::read_from_file(entry.path()) {
-                nutexb.optimize_size();
-                // TODO: log errors
-                let _ = nutexb.write_to_file(entry.path());
-            }
+    fn batch_file(&self) -> BatchFile {
+        BatchFile {
+            path: self.path.clone(),
+            layer_paths: self.layer_paths.clone(),
+            settings: self.output_settings(),
+            internal_name: Some(self.internal_name.clone()),
+            subfolder: PathBuf::new(),
         }
     }
-}
 
-fn convert_and_save_file(
-    output_folder: &Path,
-    file: &ImageFileSettings,
-    image_file: &ImageFile,
-    overrides: &FileSettingsOverrides,
-) -> Result<(), Box<dyn Error>> {
-    // Global overrides take priority over file specific settings if enabled.
-    let file_type = overrides.output_file_type.unwrap_or(file.output_file_type);
-    let format = overrides.output_format.unwrap_or(file.output_format);
-    let quality = overrides.output_quality.unwrap_or(file.output_quality);
-    let mipmaps = overrides.mipmaps.unwrap_or(file.output_mipmaps);
-
-    let output = output_folder
-        .join(file.file_name_no_extension())
-        .with_extension(file_type.extension());
-
-    match file_type {
-        ImageFileType::Dds => image_file.save_dds(&output, format, quality, mipmaps)?,
-        ImageFileType::Png => image_file.save_image(&output)?,
-        ImageFileType::Tiff => image_file.save_image(&output)?,
-        ImageFileType::Nutexb => image_file.save_nutexb(&output, format, quality, mipmaps)?,
-        ImageFileType::Bntx => image_file.save_bntx(&output, format, quality, mipmaps)?,
+    /// Check the output settings for problems in game.
+    pub fn validate(&self, overrides: &OutputOverrides) -> Vec<ValidationIssue> {
+        let settings = overrides.apply(self.output_settings());
+        let file_type = settings.file_type;
+        let (width, height, depth, _) = self.dimensions;
+        let texture = TextureProperties {
+            container: file_type.container(),
+            format: settings.format,
+            dimensions: (width, height, depth),
+            // Only an exact mipmap count can be too large for the dimensions.
+            mipmap_count: match settings.mipmaps {
+                Mipmaps::GeneratedExactly(count) => Some(count),
+                _ => None,
+            },
+            internal_name: file_type
+                .has_internal_name()
+                .then_some(self.internal_name.as_str()),
+        };
+        validate(&self.path, &texture, self.expected_color_space)
     }
-    Ok(())
+}
+
+pub fn optimize_nutexb_files() -> Option<Vec<String>> {
+    let folder = FileDialog::new()
+        .set_title("Select Nutexb Root Folder")
+        .pick_folder()?;
+
+    let report = ultimate_tex_lib::optimize_nutexb_files(&find_nutexb_files(&folder), false);
+
+    let mut messages = vec![format!(
+        "Optimized {} of {} nutexb file(s) and saved {} bytes",
+        report.files.len(),
+        report.files.len() + report.errors.len(),
+        report.bytes_saved()
+    )];
+    messages.extend(
+        report
+            .errors
+            .iter()
+            .map(|(path, e)| format!("Error optimizing {}: {e}", path.display())),
+    );
+    Some(messages)
 }
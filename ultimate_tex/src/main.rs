@@ -1,6 +1,8 @@
 // Prevents additional console window on Windows in release.
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::{sync::Arc, time::Duration};
+
 use dioxus::html::FileData;
 use dioxus::prelude::*;
 use dioxus_desktop::{Config, WindowBuilder, tao::window::Icon};
@@ -10,9 +12,13 @@ use rfd::FileDialog;
 use strum::IntoEnumIterator;
 
 mod app;
-use app::{App, ImageFileType, optimize_nutexb_files};
+use app::{App, open_presets_file, optimize_nutexb_files};
+use ultimate_tex_lib::{BatchProgress, ImageExport, ImageFileType};
+
+mod pack;
+use pack::PackChannels;
 
-use crate::app::{load_files, pick_files};
+use crate::app::{LoadedFiles, load_files, load_thumbnails, pick_files};
 
 fn main() {
     let image = image_dds::image::load_from_memory(include_bytes!("../icons/32x32.png")).unwrap();
@@ -46,6 +52,9 @@ fn app() -> Element {
     let mut is_batch_open = use_signal(|| false);
     let mut is_help_open = use_signal(|| false);
     let mut is_exporting = use_signal(|| false);
+    let mut export_progress = use_signal(|| None::<Arc<BatchProgress>>);
+    // The completed count, total count, and current file name for the export progress bar.
+    let mut export_status = use_signal(|| (0, 0, String::new()));
 
     // TODO: Clean up into more components?
     // Reduced options for global presets.
@@ -61,12 +70,25 @@ fn app() -> Element {
     ];
     let preset_mipmap_types = [
         (Mipmaps::GeneratedAutomatic, "Enabled"),
+        (Mipmaps::FromSurface, "Keep Source"),
         (Mipmaps::Disabled, "Disabled"),
     ];
+    let image_export_types = [
+        (ImageExport::Base, "First layer only"),
+        (ImageExport::Layers, "All layers and cube map faces"),
+        (ImageExport::DepthSlices, "All depth slices of 3D textures"),
+        (
+            ImageExport::Channels,
+            "Each channel to separate grayscale files",
+        ),
+    ];
 
     let save_in_same_folder = app.read().settings.save_in_same_folder;
+    let image_export = app.read().settings.image_export;
+    let report_quality = app.read().settings.report_quality;
+    let save_heatmaps = app.read().settings.save_heatmaps;
 
-    let override_output_file_type = app.read().settings.overrides.output_file_type;
+    let override_output_file_type = app.read().settings.overrides.file_type;
     let show_compressed_options = override_output_file_type
         .map(is_compressed_type)
         .unwrap_or(true);
@@ -87,12 +109,8 @@ fn app() -> Element {
 
         spawn({
             async move {
-                if let Some((new_thumbnails, new_settings)) =
-                    tokio::task::spawn_blocking(pick_files).await.unwrap()
-                {
-                    app.with_mut(|a| {
-                        add_image_files(a, new_thumbnails, new_settings);
-                    });
+                if let Some(loaded) = tokio::task::spawn_blocking(pick_files).await.unwrap() {
+                    add_image_files(app, messages, loaded);
                 }
             }
         });
@@ -100,12 +118,10 @@ fn app() -> Element {
 
     let add_dropped_files = move |files: Vec<FileData>| async move {
         let paths = files.iter().map(|f| f.path()).collect();
-        let (new_thumbnails, new_settings) = tokio::task::spawn_blocking(move || load_files(paths))
+        let loaded = tokio::task::spawn_blocking(move || load_files(paths))
             .await
             .unwrap();
-        app.with_mut(|a| {
-            add_image_files(a, new_thumbnails, new_settings);
-        });
+        add_image_files(app, messages, loaded);
     };
 
     use_effect(|| {
@@ -131,15 +147,28 @@ fn app() -> Element {
         spawn({
             async move {
                 is_exporting.set(true);
+                let progress = Arc::new(BatchProgress::new());
+                export_progress.set(Some(progress.clone()));
 
                 // The app doesn't store image data, so this clone is cheap.
                 let app = app.read().clone();
-                let new_messages =
-                    tokio::task::spawn_blocking(move || app.convert_and_export_files().unwrap())
-                        .await
-                        .unwrap();
+                let task = tokio::task::spawn_blocking({
+                    let progress = progress.clone();
+                    move || app.convert_and_export_files(&progress)
+                });
+
+                // Poll the progress to avoid blocking the UI while converting.
+                while !task.is_finished() {
+                    let current_file = progress
+                        .current_file()
+                        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+                        .unwrap_or_default();
+                    export_status.set((progress.completed(), progress.total(), current_file));
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
 
-                *messages.write() = new_messages;
+                *messages.write() = task.await.unwrap();
+                export_progress.set(None);
                 is_exporting.set(false);
             }
         });
@@ -173,6 +202,15 @@ fn app() -> Element {
                                 },
                                 "Clear Files..."
                             }
+                            li {
+                                onclick: move |_| {
+                                    is_file_open.set(false);
+                                    if let Err(e) = open_presets_file() {
+                                        *messages.write() = vec![format!("Error opening presets: {e}")];
+                                    }
+                                },
+                                "Edit Presets..."
+                            }
                         }
                     }
                 }
@@ -189,7 +227,9 @@ fn app() -> Element {
                         ul { role: "listbox",
                             li {
                                 onclick: move |_| {
-                                    optimize_nutexb_files();
+                                    if let Some(new_messages) = optimize_nutexb_files() {
+                                        *messages.write() = new_messages;
+                                    }
                                     is_batch_open.set(false);
                                 },
                                 "Optimize Nutexb Padding..."
@@ -232,6 +272,45 @@ fn app() -> Element {
             }
             "Save to original folder"
         }
+        fieldset {
+            legend {
+                strong { "PNG/TIFF/EXR Export" }
+            }
+            for (i , (option , option_name)) in image_export_types.into_iter().enumerate() {
+                label { r#for: "imageExport{i}",
+                    input {
+                        r#type: "radio",
+                        id: "imageExport{i}",
+                        name: "imageExport",
+                        checked: image_export == option,
+                        oninput: move |_| {
+                            app.with_mut(|a| a.settings.image_export = option);
+                        },
+                    }
+                    {option_name}
+                }
+            }
+        }
+        label {
+            input {
+                r#type: "checkbox",
+                checked: "{report_quality}",
+                onchange: move |e| {
+                    app.with_mut(|a| a.settings.report_quality = e.value().parse().unwrap());
+                },
+            }
+            "Report PSNR/SSIM quality for DDS/Nutexb/Bntx exports"
+        }
+        label {
+            input {
+                r#type: "checkbox",
+                checked: "{save_heatmaps}",
+                onchange: move |e| {
+                    app.with_mut(|a| a.settings.save_heatmaps = e.value().parse().unwrap());
+                },
+            }
+            "Save difference heatmaps for DDS/Nutexb/Bntx exports"
+        }
         if !save_in_same_folder {
             // TODO: use an input with type directory instead?
             div { class: "grid-horizontal",
@@ -258,10 +337,29 @@ fn app() -> Element {
                 onclick: export_files,
                 "Export"
             }
-            for message in messages.read().iter() {
-                div { class: "message-text", "{message}" }
+            if let Some(job_progress) = export_progress() {
+                progress {
+                    style: "width: 300px; margin: 0px 10px;",
+                    value: "{export_status().0}",
+                    max: "{export_status().1}",
+                }
+                div { class: "message-text",
+                    "{export_status().0} of {export_status().1} file(s) {export_status().2}"
+                }
+                button {
+                    style: "width: auto;",
+                    class: "secondary",
+                    disabled: job_progress.is_cancelled(),
+                    onclick: move |_| job_progress.cancel(),
+                    "Cancel"
+                }
+            } else {
+                for message in messages.read().iter() {
+                    div { class: "message-text", "{message}" }
+                }
             }
         }
+        PackChannels { app, messages }
         hr {}
 
         div { class: "flex-container",
@@ -279,7 +377,7 @@ fn app() -> Element {
                             checked: override_output_file_type == Some(option),
                             oninput: move |e| {
                                 app.with_mut(|a| {
-                                    a.settings.overrides.output_file_type = Some(e.value().parse().unwrap());
+                                    a.settings.overrides.file_type = Some(e.value().parse().unwrap());
                                 });
                             },
                         }
@@ -294,7 +392,7 @@ fn app() -> Element {
                         value: "",
                         checked: override_output_file_type.is_none(),
                         oninput: move |_| {
-                            app.with_mut(|a| a.settings.overrides.output_file_type = None);
+                            app.with_mut(|a| a.settings.overrides.file_type = None);
                         },
                     }
                     "Custom..."
@@ -313,10 +411,10 @@ fn app() -> Element {
                                 id: "outputFormat{option}",
                                 name: "outputFormat",
                                 value: "{option}",
-                                checked: app.read().settings.overrides.output_format == Some(option),
+                                checked: app.read().settings.overrides.format == Some(option),
                                 oninput: move |e| {
                                     app.with_mut(|a| {
-                                        a.settings.overrides.output_format = Some(e.value().parse().unwrap());
+                                        a.settings.overrides.format = Some(e.value().parse().unwrap());
                                     });
                                 },
                             }
@@ -329,9 +427,9 @@ fn app() -> Element {
                             id: "outputFormatNull",
                             name: "outputFormat",
                             value: "",
-                            checked: app.read().settings.overrides.output_format.is_none(),
+                            checked: app.read().settings.overrides.format.is_none(),
                             oninput: move |_| {
-                                app.with_mut(|a| a.settings.overrides.output_format = None);
+                                app.with_mut(|a| a.settings.overrides.format = None);
                             },
                         }
                         "Custom..."
@@ -383,10 +481,10 @@ fn app() -> Element {
                                 id: "compression{option}",
                                 name: "compression",
                                 value: "{option}",
-                                checked: app.read().settings.overrides.output_quality == Some(option),
+                                checked: app.read().settings.overrides.quality == Some(option),
                                 oninput: move |e| {
                                     app.with_mut(|a| {
-                                        a.settings.overrides.output_quality = Some(e.value().parse().unwrap());
+                                        a.settings.overrides.quality = Some(e.value().parse().unwrap());
                                     });
                                 },
                             }
@@ -399,9 +497,9 @@ fn app() -> Element {
                             id: "compressionNull",
                             name: "compression",
                             value: "",
-                            checked: app.read().settings.overrides.output_quality.is_none(),
+                            checked: app.read().settings.overrides.quality.is_none(),
                             oninput: move |_| {
-                                app.with_mut(|a| a.settings.overrides.output_quality = None);
+                                app.with_mut(|a| a.settings.overrides.quality = None);
                             },
                         }
                         "Custom..."
@@ -438,6 +536,9 @@ fn app() -> Element {
                         th { scope: "col",
                             strong { "Mipmaps" }
                         }
+                        th { scope: "col",
+                            strong { "Internal Name" }
+                        }
                         th {}
                     }
                 }
@@ -447,9 +548,24 @@ fn app() -> Element {
                             td {
                                 img { src: "{app.read().png_thumbnails[i]}" }
                             }
-                            td { "{item.name}" }
-                            td { "{item.format}" }
-                            td { "{item.dimensions.0}x{item.dimensions.1}x{item.dimensions.2}" }
+                            td {
+                                "{item.name}"
+                                for issue in item.validate(&app.read().settings.overrides) {
+                                    div { class: "warning-text", "{issue}" }
+                                }
+                            }
+                            td {
+                                "{item.format}"
+                                if let Some(bits) = item.bits_per_channel {
+                                    " ({bits}-bit)"
+                                }
+                            }
+                            td {
+                                "{item.dimensions.0}x{item.dimensions.1}x{item.dimensions.2}"
+                                if item.dimensions.3 > 1 {
+                                    " ({item.dimensions.3} layers)"
+                                }
+                            }
                             td {
                                 match override_output_file_type {
                                     Some(ty) => rsx! { "{ty}" },
@@ -468,7 +584,7 @@ fn app() -> Element {
                                 }
                             }
                             td {
-                                match app.read().settings.overrides.output_format {
+                                match app.read().settings.overrides.format {
                                     Some(ty) => rsx! { "{ty}" },
                                     None => rsx! {
                                         select {
@@ -486,7 +602,7 @@ fn app() -> Element {
                                 }
                             }
                             td {
-                                match app.read().settings.overrides.output_quality {
+                                match app.read().settings.overrides.quality {
                                     Some(ty) => rsx! { "{ty}" },
                                     None => rsx! {
                                         select {
@@ -521,6 +637,16 @@ fn app() -> Element {
                                     },
                                 }
                             }
+                            td {
+                                input {
+                                    r#type: "text",
+                                    disabled: !override_output_file_type.unwrap_or(item.output_file_type).has_internal_name(),
+                                    value: "{item.internal_name}",
+                                    oninput: move |e| {
+                                        app.with_mut(|a| a.settings.file_settings[i].internal_name = e.value());
+                                    },
+                                }
+                            }
                             td {
                                 button {
                                     class: "secondary",
@@ -556,25 +682,56 @@ fn app() -> Element {
     }
 }
 
-fn add_image_files(
-    a: &mut App,
-    new_thumbnails: Vec<String>,
-    new_settings: Vec<app::ImageFileSettings>,
-) {
+fn add_image_files(mut app: Signal<App>, mut messages: Signal<Vec<String>>, loaded: LoadedFiles) {
+    // Show why files are missing from the list.
+    if !loaded.errors.is_empty() {
+        *messages.write() = loaded.errors;
+    }
+
     // Prevent adding duplicate paths.
-    for (thumbnail, settings) in new_thumbnails.into_iter().zip(new_settings) {
-        if !a
-            .settings
-            .file_settings
-            .iter()
-            .any(|t| t.name == settings.name)
-        {
-            a.png_thumbnails.push(thumbnail);
-            a.settings.file_settings.push(settings);
+    let mut new_settings = Vec::new();
+    app.with_mut(|a| {
+        for settings in loaded.settings {
+            if !a
+                .settings
+                .file_settings
+                .iter()
+                .any(|t| t.name == settings.name)
+            {
+                a.png_thumbnails.push(String::new());
+                a.settings.file_settings.push(settings.clone());
+                new_settings.push(settings);
+            }
         }
-    }
+    });
+
+    // Show the files right away and add thumbnails once they finish decoding.
+    spawn(async move {
+        let thumbnails = tokio::task::spawn_blocking(move || {
+            let thumbnails = load_thumbnails(&new_settings);
+            new_settings
+                .into_iter()
+                .map(|s| s.name)
+                .zip(thumbnails)
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap();
+
+        app.with_mut(|a| {
+            for (name, thumbnail) in thumbnails {
+                // Files may have been removed while loading thumbnails.
+                if let Some(i) = a.settings.file_settings.iter().position(|s| s.name == name) {
+                    a.png_thumbnails[i] = thumbnail;
+                }
+            }
+        });
+    });
 }
 
 fn is_compressed_type(ty: ImageFileType) -> bool {
-    ty != ImageFileType::Png && ty != ImageFileType::Tiff
+    !matches!(
+        ty,
+        ImageFileType::Png | ImageFileType::Tiff | ImageFileType::Exr
+    )
 }
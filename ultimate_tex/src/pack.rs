@@ -0,0 +1,199 @@
+use std::path::PathBuf;
+
+use dioxus::prelude::*;
+use rfd::FileDialog;
+use strum::IntoEnumIterator;
+use ultimate_tex_lib::{Channel, ChannelSource, Error as LibError, PackChannel, pack_channels};
+
+use crate::{
+    add_image_files,
+    app::{App, load_files},
+};
+
+#[derive(Clone)]
+struct PackChannelSettings {
+    output_channel: Channel,
+    /// The image to use instead of the constant value.
+    path: Option<PathBuf>,
+    channel: Channel,
+    constant: f32,
+    invert: bool,
+}
+
+impl PackChannelSettings {
+    fn new(output_channel: Channel) -> Self {
+        Self {
+            output_channel,
+            path: None,
+            channel: Channel::R,
+            // Default to opaque alpha.
+            constant: if output_channel == Channel::A {
+                1.0
+            } else {
+                0.0
+            },
+            invert: false,
+        }
+    }
+
+    fn pack_channel(&self) -> Result<PackChannel, LibError> {
+        let source = match &self.path {
+            Some(path) => ChannelSource::Image {
+                image: image_dds::image::open(path)?,
+                channel: self.channel,
+            },
+            None => ChannelSource::Constant(self.constant),
+        };
+        Ok(PackChannel {
+            source,
+            invert: self.invert,
+        })
+    }
+}
+
+#[component]
+pub fn PackChannels(app: Signal<App>, messages: Signal<Vec<String>>) -> Element {
+    let mut channels = use_signal(|| {
+        Channel::iter()
+            .map(PackChannelSettings::new)
+            .collect::<Vec<_>>()
+    });
+
+    let save_packed_image = move |_| {
+        spawn(async move {
+            let channels = channels.read().clone();
+            let result = tokio::task::spawn_blocking(move || {
+                let output = FileDialog::new()
+                    .add_filter("Image Files", &["png", "tiff", "exr"])
+                    .set_file_name("packed.png")
+                    .save_file()?;
+                Some(save_packed(&channels, output))
+            })
+            .await
+            .unwrap();
+
+            match result {
+                Some(Ok(output)) => {
+                    // Add the packed image to convert it to nutexb or other formats.
+                    let message = format!("Saved packed image to {}", output.display());
+                    let loaded = tokio::task::spawn_blocking(move || load_files(vec![output]))
+                        .await
+                        .unwrap();
+                    *messages.write() = vec![message];
+                    add_image_files(app, messages, loaded);
+                }
+                Some(Err(e)) => *messages.write() = vec![format!("Error packing channels: {e}")],
+                None => (),
+            }
+        });
+    };
+
+    rsx! {
+        details {
+            summary { "Pack Channels" }
+            table { role: "grid",
+                thead {
+                    tr {
+                        th { scope: "col",
+                            strong { "Output" }
+                        }
+                        th { scope: "col",
+                            strong { "Source" }
+                        }
+                        th { scope: "col",
+                            strong { "Channel" }
+                        }
+                        th { scope: "col",
+                            strong { "Value" }
+                        }
+                        th { scope: "col",
+                            strong { "Invert" }
+                        }
+                    }
+                }
+                tbody {
+                    for (i , item) in channels.read().iter().enumerate() {
+                        tr { key: "{item.output_channel}",
+                            td { "{item.output_channel}" }
+                            td {
+                                div { class: "grid-horizontal",
+                                    button {
+                                        class: "secondary",
+                                        onclick: move |_| {
+                                            if let Some(path) = FileDialog::new()
+                                                .add_filter("Image Files", &["png", "tga", "tif", "tiff", "jpeg", "jpg", "exr", "hdr"])
+                                                .pick_file()
+                                            {
+                                                channels.with_mut(|c| c[i].path = Some(path));
+                                            }
+                                        },
+                                        "Select Image..."
+                                    }
+                                    if let Some(path) = &item.path {
+                                        button {
+                                            class: "secondary outline",
+                                            onclick: move |_| {
+                                                channels.with_mut(|c| c[i].path = None);
+                                            },
+                                            "Clear"
+                                        }
+                                        {path.file_name().unwrap_or_default().to_string_lossy().to_string()}
+                                    } else {
+                                        "Constant"
+                                    }
+                                }
+                            }
+                            td {
+                                select {
+                                    disabled: item.path.is_none(),
+                                    onchange: move |e| {
+                                        channels.with_mut(|c| c[i].channel = e.value().parse().unwrap());
+                                    },
+                                    for variant in Channel::iter() {
+                                        option { selected: item.channel == variant, value: "{variant}", "{variant}" }
+                                    }
+                                }
+                            }
+                            td {
+                                input {
+                                    r#type: "number",
+                                    min: "0",
+                                    max: "1",
+                                    step: "0.01",
+                                    disabled: item.path.is_some(),
+                                    value: "{item.constant}",
+                                    onchange: move |e| {
+                                        if let Ok(value) = e.value().parse::<f32>() {
+                                            channels.with_mut(|c| c[i].constant = value.clamp(0.0, 1.0));
+                                        }
+                                    },
+                                }
+                            }
+                            td {
+                                input {
+                                    r#type: "checkbox",
+                                    checked: "{item.invert}",
+                                    onchange: move |e| {
+                                        channels.with_mut(|c| c[i].invert = e.value().parse().unwrap());
+                                    },
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            button { onclick: save_packed_image, "Save Packed Image..." }
+        }
+    }
+}
+
+fn save_packed(channels: &[PackChannelSettings], output: PathBuf) -> Result<PathBuf, LibError> {
+    let image = pack_channels([
+        channels[0].pack_channel()?,
+        channels[1].pack_channel()?,
+        channels[2].pack_channel()?,
+        channels[3].pack_channel()?,
+    ])?;
+    image.save_image(&output)?;
+    Ok(output)
+}
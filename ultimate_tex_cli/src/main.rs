@@ -1,66 +1,1051 @@
-use std::{path::Path, str::FromStr};
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+    time::Duration,
+};
 
-use clap::Parser;
-use image_dds::Mipmaps;
-use ultimate_tex_lib::ImageFile;
+use clap::{
+    Args, Parser, Subcommand,
+    builder::{PossibleValue, PossibleValuesParser, TypedValueParser},
+};
+use image_dds::{ImageFormat, Mipmaps, Quality};
+use strum::IntoEnumIterator;
+use ultimate_tex_lib::{
+    BatchFile, BatchJob, BatchOutput, BatchProgress, BntxArchive, Channel, ChannelSource,
+    Container, ImageExport, ImageFile, ImageFileType, OutputOverrides, OutputSettings, PackChannel,
+    Presets, SaveOptions, Slices, TextureInfo, compare, depth_slice_path, find_input_files,
+    find_nutexb_files, internal_name_matches_file_name, optimize_nutexb_files, pack_channels,
+    validate_file,
+};
+
+// The output file type is determined by the extension or the batch output type.
+const DEFAULT_SETTINGS: OutputSettings = OutputSettings {
+    file_type: ImageFileType::Nutexb,
+    format: ImageFormat::BC7RgbaUnorm,
+    quality: Quality::Fast,
+    mipmaps: Mipmaps::GeneratedAutomatic,
+};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about = "Smash Ultimate texture converter", long_about = None)]
-struct Args {
-    #[arg(help = "The input image file to convert")]
-    input: String,
+#[command(
+    author,
+    version,
+    about = "Smash Ultimate texture converter",
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    #[arg(help = "The output converted image file")]
-    output: String,
+    #[command(flatten)]
+    convert: ConvertArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(
+        about = "Convert multiple files, folders, or glob patterns in parallel to a single file type"
+    )]
+    Batch(BatchArgs),
+    #[command(about = "Pack channels from separate images into a single texture like a PRM map")]
+    Pack(PackArgs),
+    #[command(
+        about = "Check textures for problems in game like unsupported formats or mismatched names"
+    )]
+    Validate(ValidateArgs),
+    #[command(
+        about = "Compare the properties and pixels of two textures in any supported file format"
+    )]
+    Diff(DiffArgs),
+    #[command(
+        about = "Print the format, dimensions, and other properties without decoding the image data"
+    )]
+    Info(InfoArgs),
+    #[command(
+        about = "Remove unnecessary padding from nutexb files without changing the image data"
+    )]
+    Optimize(OptimizeArgs),
+    #[command(
+        about = "List, export, or replace the textures in BNTX files with multiple textures like UI archives"
+    )]
+    Bntx(BntxArgs),
+}
+
+#[derive(Args, Debug)]
+struct ConvertArgs {
+    #[arg(required = true, help = "The input image file to convert")]
+    input: Option<String>,
+
+    #[arg(required = true, help = "The output converted image file")]
+    output: Option<String>,
+
+    #[command(flatten)]
+    encode: EncodeArgs,
+
+    #[arg(
+        long = "all-layers",
+        help = "Save each array layer or cube map face to a separate image with a layer suffix"
+    )]
+    all_layers: bool,
+
+    #[arg(
+        long = "depth-slices",
+        conflicts_with_all = ["all_layers", "depth_atlas"],
+        help = "Save each depth slice of a 3D texture to a separate image with a suffix like lut_z0.png"
+    )]
+    depth_slices: bool,
+
+    #[arg(
+        long = "depth-atlas",
+        conflicts_with = "all_layers",
+        help = "Save the depth slices of a 3D texture tiled into a single image"
+    )]
+    depth_atlas: bool,
+
+    #[arg(
+        long = "split-channels",
+        conflicts_with_all = ["all_layers", "depth_slices", "depth_atlas"],
+        help = "Save each RGBA channel to a separate grayscale image with a channel suffix like img_r.png"
+    )]
+    split_channels: bool,
+
+    #[arg(
+        long = "from-depth-slices",
+        conflicts_with = "from_depth_atlas",
+        help = "Create a 3D texture from images like lut_z0.png, lut_z1.png, ... for the input lut.png"
+    )]
+    from_depth_slices: bool,
+
+    #[arg(
+        long = "from-depth-atlas",
+        value_name = "DEPTH",
+        help = "Create a 3D texture with DEPTH slices from the tiled input image"
+    )]
+    from_depth_atlas: Option<u32>,
+
+    #[arg(
+        long = "atlas-columns",
+        value_name = "COLUMNS",
+        help = "The number of depth slices in each row of an atlas [default: all slices in a single row]"
+    )]
+    atlas_columns: Option<u32>,
+}
+
+#[derive(Args, Debug)]
+struct EncodeArgs {
+    #[command(flatten)]
+    output: OutputArgs,
+
+    #[arg(
+        long = "internal-name",
+        value_name = "NAME",
+        help = "The name stored in nutexb and bntx files [default: the existing nutexb or bntx name or the output file name]"
+    )]
+    internal_name: Option<String>,
+
+    #[arg(
+        long = "metrics",
+        help = "Print the PSNR, SSIM, and max error per channel after encoding nutexb, bntx, or dds files"
+    )]
+    metrics: bool,
 
-    // TODO: make this a value enum to show possible image formats?
+    #[arg(
+        long = "heatmap",
+        value_name = "FILE",
+        help = "Save an image of the difference between the input and encoded nutexb, bntx, or dds file"
+    )]
+    heatmap: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct OutputArgs {
     #[arg(
         short = 'f',
         long = "format",
+        value_parser = image_format_parser(),
         help = "The output image format for files supporting compression"
     )]
-    format: Option<String>,
+    format: Option<ImageFormat>,
+
+    #[arg(
+        short = 'q',
+        long = "quality",
+        value_parser = quality_parser(),
+        help = "The compression quality for files supporting compression. Higher quality is slower to encode [default: fast]"
+    )]
+    quality: Option<Quality>,
+
+    #[arg(
+        long = "mipmaps",
+        value_name = "MIPMAPS",
+        value_parser = parse_mipmaps,
+        help = "Generate mipmaps automatically with auto, disable mipmaps with none, keep the existing mipmaps with keep, or generate exactly N mip levels including the base level [default: auto]"
+    )]
+    mipmaps: Option<Mipmaps>,
 
     #[arg(
         long = "no-mipmaps",
-        help = "Disable mipmap generation and only include the base mip level"
+        conflicts_with = "mipmaps",
+        help = "Disable mipmap generation and only include the base mip level. Same as --mipmaps none"
     )]
     no_mipmaps: bool,
+
+    #[arg(
+        long = "keep-mipmaps",
+        conflicts_with_all = ["no_mipmaps", "mipmaps"],
+        help = "Keep the existing mipmaps from the input file instead of generating new mipmaps. Same as --mipmaps keep"
+    )]
+    keep_mipmaps: bool,
+
+    #[arg(
+        long = "presets",
+        value_name = "FILE",
+        help = "A JSON file with rules for choosing the format and mipmaps from the output file name [default: Smash Ultimate name suffixes like _col or _nor]"
+    )]
+    presets: Option<PathBuf>,
+
+    #[arg(
+        long = "no-presets",
+        conflicts_with = "presets",
+        help = "Disable choosing the format and mipmaps from the output file name"
+    )]
+    no_presets: bool,
+}
+
+#[derive(Args, Debug)]
+struct BatchArgs {
+    #[arg(
+        required = true,
+        help = "The input files, folders to search recursively, or quoted glob patterns like \"fighter/**/*.png\""
+    )]
+    inputs: Vec<String>,
+
+    #[arg(
+        short = 't',
+        long = "type",
+        value_name = "TYPE",
+        value_parser = parse_file_type,
+        help = "The output file type: nutexb, bntx, dds, png, tiff, or exr"
+    )]
+    file_type: ImageFileType,
+
+    #[arg(
+        short = 'o',
+        long = "output-dir",
+        value_name = "DIR",
+        help = "The folder for converted files with the same subfolders as the input folders or patterns [default: the folder of each input file]"
+    )]
+    output_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    output: OutputArgs,
+
+    #[arg(
+        long = "all-layers",
+        help = "Save each array layer or cube map face to a separate image with a layer suffix"
+    )]
+    all_layers: bool,
+
+    #[arg(
+        long = "depth-slices",
+        conflicts_with = "all_layers",
+        help = "Save each depth slice of a 3D texture to a separate image with a suffix like lut_z0.png"
+    )]
+    depth_slices: bool,
+
+    #[arg(
+        long = "split-channels",
+        conflicts_with_all = ["all_layers", "depth_slices"],
+        help = "Save each RGBA channel to a separate grayscale image with a channel suffix like img_r.png"
+    )]
+    split_channels: bool,
+
+    #[arg(
+        long = "metrics",
+        help = "Print the PSNR, SSIM, and max error per channel after encoding nutexb, bntx, or dds files"
+    )]
+    metrics: bool,
+
+    #[arg(
+        long = "heatmaps",
+        help = "Save an image of the difference between each input and encoded nutexb, bntx, or dds file like img_diff.png"
+    )]
+    heatmaps: bool,
+}
+
+#[derive(Args, Debug)]
+struct PackArgs {
+    #[arg(help = "The output packed image file")]
+    output: String,
+
+    #[arg(
+        short = 'r',
+        long = "red",
+        value_name = "SOURCE",
+        value_parser = parse_channel_arg,
+        help = "The red channel as a value from 0.0 to 1.0 or an image with an optional channel like metal.png:r [default: 0.0]"
+    )]
+    red: Option<ChannelArg>,
+
+    #[arg(
+        short = 'g',
+        long = "green",
+        value_name = "SOURCE",
+        value_parser = parse_channel_arg,
+        help = "The green channel as a value from 0.0 to 1.0 or an image with an optional channel like rough.png:r [default: 0.0]"
+    )]
+    green: Option<ChannelArg>,
+
+    #[arg(
+        short = 'b',
+        long = "blue",
+        value_name = "SOURCE",
+        value_parser = parse_channel_arg,
+        help = "The blue channel as a value from 0.0 to 1.0 or an image with an optional channel like ao.png:r [default: 0.0]"
+    )]
+    blue: Option<ChannelArg>,
+
+    #[arg(
+        short = 'a',
+        long = "alpha",
+        value_name = "SOURCE",
+        value_parser = parse_channel_arg,
+        help = "The alpha channel as a value from 0.0 to 1.0 or an image with an optional channel like spec.png:r [default: 1.0]"
+    )]
+    alpha: Option<ChannelArg>,
+
+    #[arg(
+        long = "invert",
+        value_name = "CHANNELS",
+        value_delimiter = ',',
+        help = "The output channels to invert like r,g"
+    )]
+    invert: Vec<Channel>,
+
+    #[command(flatten)]
+    encode: EncodeArgs,
+}
+
+#[derive(Args, Debug)]
+struct ValidateArgs {
+    #[arg(required = true, help = "The nutexb, bntx, or dds files to check")]
+    files: Vec<PathBuf>,
+
+    #[arg(
+        long = "presets",
+        value_name = "FILE",
+        help = "A JSON file with rules for the expected color space from the file name [default: Smash Ultimate name suffixes like _col or _nor]"
+    )]
+    presets: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct DiffArgs {
+    #[arg(help = "The first texture file like an original nutexb")]
+    a: PathBuf,
+
+    #[arg(help = "The second texture file like a re-exported nutexb")]
+    b: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct InfoArgs {
+    #[arg(required = true, help = "The texture files")]
+    files: Vec<PathBuf>,
+
+    #[arg(
+        long = "json",
+        help = "Print the properties for all files as a JSON array with the path for each file"
+    )]
+    json: bool,
 }
 
-fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
-    let input = Path::new(&args.input);
-    let output = Path::new(&args.output);
+#[derive(Args, Debug)]
+struct OptimizeArgs {
+    #[arg(
+        required = true,
+        help = "The nutexb files or folders to search recursively for nutexb files"
+    )]
+    paths: Vec<PathBuf>,
 
-    let input_image = ImageFile::from_file(input)?;
+    #[arg(
+        long = "dry-run",
+        help = "Report the bytes that would be saved without modifying any files"
+    )]
+    dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+struct BntxArgs {
+    #[command(subcommand)]
+    command: BntxCommand,
+}
 
-    let format = args
-        .format
-        .and_then(|s| image_dds::ImageFormat::from_str(&s).ok())
-        .unwrap_or(image_dds::ImageFormat::BC7RgbaUnorm);
+#[derive(Subcommand, Debug)]
+enum BntxCommand {
+    #[command(about = "Print the name, format, and dimensions of each texture")]
+    List(BntxListArgs),
+    #[command(about = "Save each texture to a separate file named after the texture")]
+    Export(BntxExportArgs),
+    #[command(about = "Replace a single texture by name without changing the other textures")]
+    Replace(BntxReplaceArgs),
+}
+
+#[derive(Args, Debug)]
+struct BntxListArgs {
+    #[arg(help = "The BNTX file")]
+    input: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct BntxExportArgs {
+    #[arg(help = "The BNTX file")]
+    input: PathBuf,
+
+    #[arg(help = "The folder for the exported textures like ui_chara_0.dds")]
+    output_dir: PathBuf,
+
+    #[arg(
+        short = 't',
+        long = "type",
+        value_name = "TYPE",
+        default_value = "dds",
+        help = "The output file type: dds to keep the existing format and mipmaps or an image type like png, tiff, or exr"
+    )]
+    file_type: String,
+}
 
-    let quality = image_dds::Quality::Fast;
+#[derive(Args, Debug)]
+struct BntxReplaceArgs {
+    #[arg(help = "The BNTX file")]
+    input: PathBuf,
 
-    let mipmaps = if args.no_mipmaps {
-        Mipmaps::Disabled
+    #[arg(help = "The name of the texture to replace")]
+    name: String,
+
+    #[arg(help = "The new image with the same dimensions as the texture")]
+    image: PathBuf,
+
+    #[arg(
+        short = 'o',
+        long = "output",
+        value_name = "FILE",
+        help = "The output BNTX file [default: overwrite the input file]"
+    )]
+    output: Option<PathBuf>,
+
+    #[arg(
+        short = 'q',
+        long = "quality",
+        value_parser = quality_parser(),
+        help = "The compression quality for compressed textures. Higher quality is slower to encode [default: fast]"
+    )]
+    quality: Option<Quality>,
+}
+
+#[derive(Debug, Clone)]
+enum ChannelArg {
+    Image(PathBuf, Channel),
+    Constant(f32),
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Batch(args)) => batch(args),
+        Some(Command::Pack(args)) => pack(args).map(|_| ExitCode::SUCCESS),
+        Some(Command::Validate(args)) => validate(args),
+        Some(Command::Diff(args)) => diff(args),
+        Some(Command::Info(args)) => info(args),
+        Some(Command::Optimize(args)) => Ok(optimize(args)),
+        Some(Command::Bntx(args)) => bntx(args).map(|_| ExitCode::SUCCESS),
+        None => convert(cli.convert).map(|_| ExitCode::SUCCESS),
+    }
+}
+
+fn convert(args: ConvertArgs) -> anyhow::Result<()> {
+    // Clap requires the input and output without a subcommand.
+    let input_path = args.input.unwrap_or_default();
+    let output_path = args.output.unwrap_or_default();
+    let input = Path::new(&input_path);
+    let output = Path::new(&output_path);
+
+    let input_image = if args.from_depth_slices {
+        ImageFile::from_slice_files(&depth_slice_paths(input), Slices::Depth)?
+    } else if let Some(depth) = args.from_depth_atlas {
+        let atlas = image_dds::image::open(input)?;
+        ImageFile::from_depth_atlas(atlas, depth, args.atlas_columns.unwrap_or(depth))?
     } else {
-        Mipmaps::GeneratedAutomatic
+        ImageFile::from_file(input)?
     };
 
-    match output
-        .extension()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_lowercase()
-        .as_str()
-    {
-        "nutexb" => input_image.save_nutexb(output, format, quality, mipmaps)?,
-        "bntx" => input_image.save_bntx(output, format, quality, mipmaps)?,
-        "dds" => input_image.save_dds(output, format, quality, mipmaps)?,
-        // Assume the other formats are image formats.
-        _ => input_image.save_image(output)?,
+    // Layer and slice options only apply to image formats.
+    let is_image = output_file_type(output).container() == Container::Image;
+    if is_image && args.depth_atlas {
+        let (_, _, depth) = input_image.dimensions();
+        let columns = args.atlas_columns.unwrap_or(depth);
+        input_image.save_depth_atlas(output, columns)?;
+    } else {
+        let image_export = if args.all_layers {
+            ImageExport::Layers
+        } else if args.depth_slices {
+            ImageExport::DepthSlices
+        } else if args.split_channels {
+            ImageExport::Channels
+        } else {
+            ImageExport::Base
+        };
+        save_file(&input_image, output, args.encode, image_export)?;
     }
     Ok(())
 }
+
+fn batch(args: BatchArgs) -> anyhow::Result<ExitCode> {
+    let presets = args.output.presets()?;
+
+    let mut files = Vec::new();
+    for input in &args.inputs {
+        let input_files = find_input_files(input);
+        if input_files.is_empty() {
+            eprintln!("Warning: no files found for {input:?}");
+        }
+        files.extend(input_files.into_iter().map(|file| BatchFile {
+            settings: presets.apply(&file.path, DEFAULT_SETTINGS),
+            path: file.path,
+            layer_paths: Vec::new(),
+            internal_name: None,
+            subfolder: file.subfolder,
+        }));
+    }
+    if files.is_empty() {
+        anyhow::bail!("no input files found");
+    }
+
+    let image_export = if args.all_layers {
+        ImageExport::Layers
+    } else if args.depth_slices {
+        ImageExport::DepthSlices
+    } else if args.split_channels {
+        ImageExport::Channels
+    } else {
+        ImageExport::Base
+    };
+
+    let job = BatchJob {
+        files,
+        overrides: OutputOverrides {
+            file_type: Some(args.file_type),
+            ..args.output.overrides()
+        },
+        output: match args.output_dir {
+            Some(folder) => BatchOutput::Folder(folder),
+            None => BatchOutput::SameFolder,
+        },
+        image_export,
+        report_quality: args.metrics,
+        save_heatmaps: args.heatmaps,
+    };
+
+    let progress = BatchProgress::new();
+    let results = std::thread::scope(|s| {
+        let task = s.spawn(|| job.run_with_progress(&progress));
+
+        // Avoid filling logs with progress updates when not running in a terminal.
+        if std::io::stderr().is_terminal() {
+            while !task.is_finished() {
+                eprint!(
+                    "\rConverted {} of {} file(s)",
+                    progress.completed(),
+                    progress.total()
+                );
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            eprintln!();
+        }
+
+        task.join().unwrap()
+    });
+
+    // Report all failures instead of stopping at the first error.
+    let mut failed_count = 0;
+    for result in &results {
+        match &result.result {
+            Ok(file) => {
+                if let Some(metrics) = file.metrics {
+                    println!("{}: {metrics}", result.path.display());
+                }
+            }
+            Err(e) => {
+                eprintln!("Error converting {}: {e}", result.path.display());
+                failed_count += 1;
+            }
+        }
+    }
+    println!(
+        "Successfully converted {} of {} file(s)",
+        results.len() - failed_count,
+        results.len()
+    );
+
+    Ok(if failed_count == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn pack(args: PackArgs) -> anyhow::Result<()> {
+    let channel = |arg: Option<ChannelArg>, default: f32, channel: Channel| {
+        let source = match arg.unwrap_or(ChannelArg::Constant(default)) {
+            ChannelArg::Image(path, channel) => ChannelSource::Image {
+                image: image_dds::image::open(path)?,
+                channel,
+            },
+            ChannelArg::Constant(value) => ChannelSource::Constant(value),
+        };
+        Ok::<_, anyhow::Error>(PackChannel {
+            source,
+            invert: args.invert.contains(&channel),
+        })
+    };
+
+    let image = pack_channels([
+        channel(args.red, 0.0, Channel::R)?,
+        channel(args.green, 0.0, Channel::G)?,
+        channel(args.blue, 0.0, Channel::B)?,
+        channel(args.alpha, 1.0, Channel::A)?,
+    ])?;
+
+    save_file(
+        &image,
+        Path::new(&args.output),
+        args.encode,
+        ImageExport::Base,
+    )
+}
+
+fn validate(args: ValidateArgs) -> anyhow::Result<ExitCode> {
+    let presets = match &args.presets {
+        Some(path) => Presets::from_file(path)?,
+        None => Presets::default(),
+    };
+
+    // Check all files before failing to report as many issues as possible.
+    let mut has_issues = false;
+    for path in &args.files {
+        match validate_file(path, &presets) {
+            Ok(issues) => {
+                for issue in &issues {
+                    println!("{}: {issue}", path.display());
+                }
+                has_issues |= !issues.is_empty();
+            }
+            Err(e) => {
+                eprintln!("Error validating {}: {e}", path.display());
+                has_issues = true;
+            }
+        }
+    }
+
+    Ok(if has_issues {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn diff(args: DiffArgs) -> anyhow::Result<ExitCode> {
+    let a = ImageFile::from_file(&args.a)?;
+    let b = ImageFile::from_file(&args.b)?;
+    let comparison = compare(&a, &b)?;
+    println!("{comparison}");
+
+    // Use a non-zero exit code like diff to make this easier to use in scripts.
+    Ok(if comparison.is_identical() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn info(args: InfoArgs) -> anyhow::Result<ExitCode> {
+    // Read all files before failing to report as many errors as possible.
+    let mut has_errors = false;
+    let mut values = Vec::new();
+    for path in &args.files {
+        let info = match TextureInfo::from_file(path) {
+            Ok(info) => info,
+            Err(e) => {
+                eprintln!("Error reading {}: {e}", path.display());
+                has_errors = true;
+                continue;
+            }
+        };
+
+        if args.json {
+            let mut value = serde_json::to_value(&info)?;
+            value["path"] = path.to_string_lossy().into();
+            values.push(value);
+        } else {
+            println!("{}:", path.display());
+            println!("  container: {}", info.container);
+            println!("  format: {}", info.format);
+            if let Some(bits) = info.bits_per_channel {
+                println!("  bits per channel: {bits}");
+            }
+            println!("  color space: {}", info.color_space);
+            println!("  dimensions: {}x{}", info.width, info.height);
+            println!("  depth: {}", info.depth);
+            println!("  layers: {}", info.layer_count);
+            println!("  mipmaps: {}", info.mipmap_count);
+            println!("  array: {}", info.is_array);
+            println!("  cube map: {}", info.is_cube);
+            if let Some(name) = &info.internal_name {
+                println!("  internal name: {name}");
+            }
+            println!("  data size: {} bytes", info.data_size);
+            println!();
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&values)?);
+    }
+
+    Ok(if has_errors {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn optimize(args: OptimizeArgs) -> ExitCode {
+    let paths: Vec<_> = args
+        .paths
+        .iter()
+        .flat_map(|path| {
+            if path.is_dir() {
+                find_nutexb_files(path)
+            } else {
+                vec![path.clone()]
+            }
+        })
+        .collect();
+
+    let report = optimize_nutexb_files(&paths, args.dry_run);
+    for file in &report.files {
+        println!(
+            "{}: {} -> {} bytes",
+            file.path.display(),
+            file.original_size,
+            file.optimized_size
+        );
+    }
+    for (path, e) in &report.errors {
+        eprintln!("Error optimizing {}: {e}", path.display());
+    }
+
+    let verb = if args.dry_run { "Would save" } else { "Saved" };
+    println!(
+        "{verb} {} bytes for {} of {} file(s)",
+        report.bytes_saved(),
+        report.files.len(),
+        paths.len()
+    );
+
+    if report.errors.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn bntx(args: BntxArgs) -> anyhow::Result<()> {
+    match args.command {
+        BntxCommand::List(args) => {
+            let archive = BntxArchive::from_file(&args.input)?;
+            for texture in archive.textures() {
+                let format = texture
+                    .format
+                    .map(|f| f.to_string())
+                    .unwrap_or_else(|| "unsupported".to_string());
+                println!(
+                    "{}: {format} {}x{}x{}, {} layer(s), {} mipmap(s)",
+                    texture.name,
+                    texture.width,
+                    texture.height,
+                    texture.depth,
+                    texture.layer_count,
+                    texture.mipmap_count
+                );
+            }
+            Ok(())
+        }
+        BntxCommand::Export(args) => bntx_export(args),
+        BntxCommand::Replace(args) => {
+            let mut archive = BntxArchive::from_file(&args.input)?;
+            let image = ImageFile::from_file(&args.image)?;
+            archive.replace_texture(&args.name, &image, args.quality.unwrap_or(Quality::Fast))?;
+            archive.save(args.output.as_ref().unwrap_or(&args.input))?;
+            Ok(())
+        }
+    }
+}
+
+fn bntx_export(args: BntxExportArgs) -> anyhow::Result<()> {
+    let archive = BntxArchive::from_file(&args.input)?;
+    std::fs::create_dir_all(&args.output_dir)?;
+
+    // Export all textures before failing to report as many errors as possible.
+    let file_type = args.file_type.to_lowercase();
+    let mut error_count = 0;
+    for texture in archive.textures() {
+        let output = args
+            .output_dir
+            .join(format!("{}.{file_type}", texture.name));
+        let result = archive.texture_image(&texture.name).and_then(|image| {
+            if file_type == "dds" {
+                // Keep the existing format and mipmaps.
+                let format = image.image_format()?;
+                image.save_dds(&output, format, Quality::Fast, Mipmaps::FromSurface)
+            } else {
+                image.save_image(&output)
+            }
+        });
+        match result {
+            Ok(()) => println!("{}", output.display()),
+            Err(e) => {
+                eprintln!("Error exporting {}: {e}", texture.name);
+                error_count += 1;
+            }
+        }
+    }
+
+    if error_count > 0 {
+        anyhow::bail!(
+            "failed to export {error_count} of {} textures",
+            archive.textures().len()
+        );
+    }
+    Ok(())
+}
+
+impl OutputArgs {
+    fn presets(&self) -> anyhow::Result<Presets> {
+        Ok(match &self.presets {
+            Some(path) => Presets::from_file(path)?,
+            None if self.no_presets => Presets { rules: Vec::new() },
+            None => Presets::default(),
+        })
+    }
+
+    // Explicitly set options take priority over presets.
+    fn overrides(&self) -> OutputOverrides {
+        let mipmaps = if self.no_mipmaps {
+            Some(Mipmaps::Disabled)
+        } else if self.keep_mipmaps {
+            Some(Mipmaps::FromSurface)
+        } else {
+            self.mipmaps
+        };
+
+        OutputOverrides {
+            file_type: None,
+            format: self.format,
+            quality: self.quality,
+            mipmaps,
+        }
+    }
+}
+
+fn save_file(
+    image: &ImageFile,
+    output: &Path,
+    args: EncodeArgs,
+    image_export: ImageExport,
+) -> anyhow::Result<()> {
+    let presets = args.output.presets()?;
+    let overrides = OutputOverrides {
+        file_type: Some(output_file_type(output)),
+        ..args.output.overrides()
+    };
+    let settings = overrides.apply(presets.apply(output, DEFAULT_SETTINGS));
+
+    let internal_name = settings
+        .file_type
+        .has_internal_name()
+        .then(|| internal_name(image, output, args.internal_name));
+    let options = SaveOptions {
+        internal_name,
+        image_export,
+        report_quality: args.metrics,
+        heatmap: args.heatmap,
+    };
+
+    // Use the same conversion as the batch command.
+    let converted = image.save_converted(output, settings, &options)?;
+    if let Some(metrics) = converted.metrics {
+        println!("{metrics}");
+    }
+    Ok(())
+}
+
+fn output_file_type(output: &Path) -> ImageFileType {
+    match extension(output).as_str() {
+        "nutexb" => ImageFileType::Nutexb,
+        "bntx" => ImageFileType::Bntx,
+        "dds" => ImageFileType::Dds,
+        // Image file types use the format from the extension to also support formats like JPEG.
+        _ => ImageFileType::Png,
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase()
+}
+
+fn image_format_parser() -> impl TypedValueParser<Value = ImageFormat> {
+    // List all formats in the help and errors instead of falling back to a default format.
+    PossibleValuesParser::new(ImageFormat::iter().map(|f| PossibleValue::new(f.to_string())))
+        .map(|s| ImageFormat::from_str(&s).unwrap())
+}
+
+fn quality_parser() -> impl TypedValueParser<Value = Quality> {
+    PossibleValuesParser::new(["fast", "normal", "slow"]).map(|s| match s.as_str() {
+        "normal" => Quality::Normal,
+        "slow" => Quality::Slow,
+        _ => Quality::Fast,
+    })
+}
+
+fn parse_mipmaps(s: &str) -> Result<Mipmaps, String> {
+    match s {
+        "auto" => Ok(Mipmaps::GeneratedAutomatic),
+        "none" => Ok(Mipmaps::Disabled),
+        "keep" => Ok(Mipmaps::FromSurface),
+        _ => match s.parse::<u32>() {
+            Ok(count) if count > 0 => Ok(Mipmaps::GeneratedExactly(count)),
+            _ => Err(format!(
+                "{s} is not auto, none, keep, or a mipmap count greater than 0"
+            )),
+        },
+    }
+}
+
+fn parse_file_type(s: &str) -> Result<ImageFileType, String> {
+    [
+        ImageFileType::Nutexb,
+        ImageFileType::Bntx,
+        ImageFileType::Dds,
+        ImageFileType::Png,
+        ImageFileType::Tiff,
+        ImageFileType::Exr,
+    ]
+    .into_iter()
+    .find(|t| t.extension() == s.to_lowercase())
+    .ok_or_else(|| format!("{s} is not one of nutexb, bntx, dds, png, tiff, or exr"))
+}
+
+fn parse_channel_arg(s: &str) -> Result<ChannelArg, String> {
+    if let Ok(value) = s.parse::<f32>() {
+        return if (0.0..=1.0).contains(&value) {
+            Ok(ChannelArg::Constant(value))
+        } else {
+            Err(format!("{value} is not in the range 0.0 to 1.0"))
+        };
+    }
+
+    // Check for a channel suffix like rough.png:g.
+    match s.rsplit_once(':') {
+        Some((path, channel)) if !path.is_empty() && channel.len() == 1 => {
+            let channel = Channel::from_str(channel).map_err(|e| e.to_string())?;
+            Ok(ChannelArg::Image(path.into(), channel))
+        }
+        _ => Ok(ChannelArg::Image(s.into(), Channel::R)),
+    }
+}
+
+fn depth_slice_paths(input: &Path) -> Vec<PathBuf> {
+    // Find consecutive slices starting from lut_z0.png.
+    (0..)
+        .map(|slice| depth_slice_path(input, slice))
+        .take_while(|path| path.exists())
+        .collect()
+}
+
+fn internal_name(image: &ImageFile, output: &Path, name: Option<String>) -> String {
+    let name = name.unwrap_or_else(|| image.default_internal_name(output));
+    if !internal_name_matches_file_name(&name, output) {
+        eprintln!("Warning: internal name {name:?} does not match the file name for {output:?}");
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::CommandFactory;
+
+    fn parse_output_args(args: &[&str]) -> Result<OutputArgs, clap::Error> {
+        let args = ["ultimate_tex_cli", "in.png", "out.nutexb"]
+            .iter()
+            .chain(args);
+        Cli::try_parse_from(args).map(|cli| cli.convert.encode.output)
+    }
+
+    #[test]
+    fn verify_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parse_mipmaps_values() {
+        assert_eq!(Ok(Mipmaps::GeneratedAutomatic), parse_mipmaps("auto"));
+        assert_eq!(Ok(Mipmaps::Disabled), parse_mipmaps("none"));
+        assert_eq!(Ok(Mipmaps::FromSurface), parse_mipmaps("keep"));
+        assert_eq!(Ok(Mipmaps::GeneratedExactly(1)), parse_mipmaps("1"));
+        assert_eq!(Ok(Mipmaps::GeneratedExactly(12)), parse_mipmaps("12"));
+    }
+
+    #[test]
+    fn parse_mipmaps_invalid() {
+        assert!(parse_mipmaps("0").is_err());
+        assert!(parse_mipmaps("-1").is_err());
+        assert!(parse_mipmaps("Auto").is_err());
+        assert!(parse_mipmaps("").is_err());
+    }
+
+    #[test]
+    fn mipmaps_arg() {
+        let args = parse_output_args(&["--mipmaps", "5"]).unwrap();
+        assert_eq!(Some(Mipmaps::GeneratedExactly(5)), args.mipmaps);
+
+        assert!(parse_output_args(&["--mipmaps", "0"]).is_err());
+        assert!(parse_output_args(&["--mipmaps", "none", "--no-mipmaps"]).is_err());
+    }
+
+    #[test]
+    fn format_arg() {
+        let args = parse_output_args(&["--format", "BC7RgbaUnormSrgb"]).unwrap();
+        assert_eq!(Some(ImageFormat::BC7RgbaUnormSrgb), args.format);
+
+        // Invalid formats shouldn't fall back to a default format.
+        assert!(parse_output_args(&["--format", "BC7"]).is_err());
+        assert!(parse_output_args(&["--format", ""]).is_err());
+    }
+
+    #[test]
+    fn quality_arg() {
+        let args = parse_output_args(&["--quality", "slow"]).unwrap();
+        assert_eq!(Some(Quality::Slow), args.quality);
+        assert!(parse_output_args(&["--quality", "best"]).is_err());
+    }
+}
@@ -0,0 +1,907 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+
+use image_dds::{ImageFormat, Mipmaps, Quality, image::DynamicImage};
+use rayon::prelude::*;
+use strum::IntoEnumIterator;
+
+use crate::{
+    Channel, Error, ImageFile, ImageFileType, OutputSettings, QualityMetrics, Slices, TextureInfo,
+    channel_path, file_name_no_extension, heatmap_path, write_dynamic_image,
+};
+
+/// Settings that replace the settings for every file in a [BatchJob] if set.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OutputOverrides {
+    pub file_type: Option<ImageFileType>,
+    pub format: Option<ImageFormat>,
+    pub quality: Option<Quality>,
+    pub mipmaps: Option<Mipmaps>,
+}
+
+impl OutputOverrides {
+    /// Replace the values in `settings` with any overridden values.
+    pub fn apply(&self, settings: OutputSettings) -> OutputSettings {
+        OutputSettings {
+            file_type: self.file_type.unwrap_or(settings.file_type),
+            format: self.format.unwrap_or(settings.format),
+            quality: self.quality.unwrap_or(settings.quality),
+            mipmaps: self.mipmaps.unwrap_or(settings.mipmaps),
+        }
+    }
+}
+
+/// A single input file for a [BatchJob].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchFile {
+    pub path: PathBuf,
+    /// The separate image for each layer if this file combines multiple images.
+    pub layer_paths: Vec<PathBuf>,
+    pub settings: OutputSettings,
+    /// The name stored in nutexb and bntx files
+    /// or [ImageFile::default_internal_name] if `None`.
+    pub internal_name: Option<String>,
+    /// The folder relative to [BatchOutput::Folder] for the output file
+    /// like the [InputFile::subfolder] from [find_input_files].
+    /// This is ignored for [BatchOutput::SameFolder].
+    pub subfolder: PathBuf,
+}
+
+impl BatchFile {
+    /// Load the file or combine the images in [BatchFile::layer_paths].
+    pub fn load(&self) -> Result<ImageFile, Error> {
+        if self.layer_paths.is_empty() {
+            ImageFile::from_file(&self.path)
+        } else {
+            ImageFile::from_slice_files(&self.layer_paths, Slices::Layers)
+        }
+    }
+
+    // The number of images when exporting `slices` read from the header without decoding.
+    fn slice_count(&self, slices: Slices) -> u32 {
+        if !self.layer_paths.is_empty() {
+            slices.count(self.layer_paths.len() as u32, 1)
+        } else {
+            // Files that can't be read fail to convert anyway.
+            TextureInfo::from_file(&self.path)
+                .map(|info| slices.count(info.layer_count, info.depth))
+                .unwrap_or(1)
+        }
+    }
+}
+
+/// The location for saved files in a [BatchJob].
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchOutput {
+    /// Save all files to a single folder.
+    Folder(PathBuf),
+    /// Save each file to the folder containing the input file.
+    SameFolder,
+}
+
+/// How to save image file types like PNG that only store a single 2D image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageExport {
+    /// Save the base mipmap of the first layer.
+    #[default]
+    Base,
+    /// Save each array layer or cube map face with [ImageFile::save_slice_images].
+    Layers,
+    /// Save each depth slice of a 3D texture with [ImageFile::save_slice_images].
+    DepthSlices,
+    /// Save each channel to a grayscale image with [ImageFile::save_channel_images].
+    Channels,
+}
+
+/// Settings for converting multiple files in parallel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchJob {
+    pub files: Vec<BatchFile>,
+    pub overrides: OutputOverrides,
+    pub output: BatchOutput,
+    pub image_export: ImageExport,
+    /// Calculate [QualityMetrics] for nutexb, bntx, and dds outputs.
+    pub report_quality: bool,
+    /// Save a difference heatmap next to nutexb, bntx, and dds outputs with [heatmap_path].
+    pub save_heatmaps: bool,
+}
+
+/// Progress and cancellation for [BatchJob::run_with_progress].
+///
+/// Share the progress with other threads using an [std::sync::Arc]
+/// to show progress or cancel the job while it is running.
+#[derive(Debug, Default)]
+pub struct BatchProgress {
+    total: AtomicUsize,
+    completed: AtomicUsize,
+    cancelled: AtomicBool,
+    current_file: Mutex<Option<PathBuf>>,
+}
+
+impl BatchProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of files in the job.
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// The number of files that finished converting with or without errors.
+    pub fn completed(&self) -> usize {
+        self.completed.load(Ordering::Relaxed)
+    }
+
+    /// The input path of the most recently started file.
+    pub fn current_file(&self) -> Option<PathBuf> {
+        self.current_file.lock().unwrap().clone()
+    }
+
+    /// Stop converting files as soon as possible.
+    ///
+    /// Files that are already saved are kept.
+    /// Remaining files return [Error::Cancelled] without saving any outputs.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn check_cancelled(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The result of converting a single file in a [BatchJob].
+#[derive(Debug)]
+pub struct BatchResult {
+    /// The input path from [BatchFile::path].
+    pub path: PathBuf,
+    pub result: Result<ConvertedFile, Error>,
+}
+
+/// The saved files for a successfully converted input file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvertedFile {
+    pub outputs: Vec<PathBuf>,
+    /// The quality for compressed outputs if [SaveOptions::report_quality] is enabled.
+    pub metrics: Option<QualityMetrics>,
+}
+
+impl BatchJob {
+    /// The output path for `file` after applying overrides.
+    pub fn output_path(&self, file: &BatchFile) -> PathBuf {
+        let folder = match &self.output {
+            BatchOutput::Folder(folder) => folder.join(&file.subfolder),
+            BatchOutput::SameFolder => file.path.parent().unwrap_or(Path::new("")).to_path_buf(),
+        };
+        let settings = self.overrides.apply(file.settings);
+        folder
+            .join(file_name_no_extension(&file.path))
+            .with_extension(settings.file_type.extension())
+    }
+
+    /// Convert all files in parallel and return the results in the same order as [BatchJob::files].
+    ///
+    /// Errors for one file don't prevent converting the remaining files.
+    /// Files with the same output path as another file or an output path that is also an input
+    /// return [Error::DuplicateOutput] or [Error::OutputIsInput] without saving anything.
+    pub fn run(&self) -> Vec<BatchResult> {
+        self.run_with_progress(&BatchProgress::new())
+    }
+
+    /// Convert all files like [BatchJob::run] while updating `progress`.
+    ///
+    /// Cancelling `progress` never leaves partially written outputs.
+    /// Files in progress are either fully saved or not saved at all.
+    pub fn run_with_progress(&self, progress: &BatchProgress) -> Vec<BatchResult> {
+        progress.total.store(self.files.len(), Ordering::Relaxed);
+
+        // Check all outputs first since files are converted in parallel.
+        let conflicts = self.output_conflicts();
+        self.files
+            .par_iter()
+            .zip(conflicts)
+            .map(|(file, conflict)| {
+                let result = progress.check_cancelled().and_then(|_| {
+                    *progress.current_file.lock().unwrap() = Some(file.path.clone());
+                    let result = match conflict {
+                        Some(e) => Err(e),
+                        None => self.convert_file(file, progress),
+                    };
+                    progress.completed.fetch_add(1, Ordering::Relaxed);
+                    result
+                });
+                BatchResult {
+                    path: file.path.clone(),
+                    result,
+                }
+            })
+            .collect()
+    }
+
+    // All paths saved for `file` including separate images and heatmaps.
+    fn output_paths(&self, file: &BatchFile) -> Vec<PathBuf> {
+        let output = self.output_path(file);
+        match self.overrides.apply(file.settings).file_type {
+            ImageFileType::Png | ImageFileType::Tiff | ImageFileType::Exr => {
+                let slices = match self.image_export {
+                    ImageExport::Base => return vec![output],
+                    ImageExport::Channels => {
+                        return Channel::iter().map(|c| channel_path(&output, c)).collect();
+                    }
+                    ImageExport::Layers => Slices::Layers,
+                    ImageExport::DepthSlices => Slices::Depth,
+                };
+                let count = file.slice_count(slices);
+                if count > 1 {
+                    (0..count).map(|i| slices.path(&output, i, count)).collect()
+                } else {
+                    vec![output]
+                }
+            }
+            ImageFileType::Dds | ImageFileType::Nutexb | ImageFileType::Bntx => {
+                if self.save_heatmaps {
+                    let heatmap = heatmap_path(&output);
+                    vec![output, heatmap]
+                } else {
+                    vec![output]
+                }
+            }
+        }
+    }
+
+    // Find outputs that would overwrite each other or files that are read by other conversions.
+    fn output_conflicts(&self) -> Vec<Option<Error>> {
+        let outputs: Vec<Vec<_>> = self
+            .files
+            .iter()
+            .map(|f| {
+                self.output_paths(f)
+                    .into_iter()
+                    .map(|path| (normalize_path(&path), path))
+                    .collect()
+            })
+            .collect();
+
+        let mut files_by_output: HashMap<&Path, Vec<usize>> = HashMap::new();
+        for (i, paths) in outputs.iter().enumerate() {
+            for (normalized, _) in paths {
+                files_by_output.entry(normalized).or_default().push(i);
+            }
+        }
+
+        let inputs: HashSet<PathBuf> = self
+            .files
+            .iter()
+            .flat_map(|f| {
+                if f.layer_paths.is_empty() {
+                    vec![normalize_path(&f.path)]
+                } else {
+                    f.layer_paths.iter().map(|p| normalize_path(p)).collect()
+                }
+            })
+            .collect();
+
+        outputs
+            .iter()
+            .enumerate()
+            .map(|(i, paths)| {
+                paths.iter().find_map(|(normalized, output)| {
+                    if inputs.contains(normalized) {
+                        Some(Error::OutputIsInput {
+                            output: output.clone(),
+                        })
+                    } else {
+                        files_by_output[normalized.as_path()]
+                            .iter()
+                            .find(|j| **j != i)
+                            .map(|j| Error::DuplicateOutput {
+                                output: output.clone(),
+                                other: self.files[*j].path.clone(),
+                            })
+                    }
+                })
+            })
+            .collect()
+    }
+
+    fn convert_file(
+        &self,
+        file: &BatchFile,
+        progress: &BatchProgress,
+    ) -> Result<ConvertedFile, Error> {
+        let image = file.load()?;
+        let output = self.output_path(file);
+        let options = SaveOptions {
+            internal_name: file.internal_name.clone(),
+            image_export: self.image_export,
+            report_quality: self.report_quality,
+            heatmap: self.save_heatmaps.then(|| heatmap_path(&output)),
+        };
+        save_converted(
+            &image,
+            &output,
+            self.overrides.apply(file.settings),
+            &options,
+            progress,
+        )
+    }
+}
+
+/// Options for saving converted files with [ImageFile::save_converted].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SaveOptions {
+    /// The name stored in nutexb and bntx files
+    /// or [ImageFile::default_internal_name] if `None`.
+    pub internal_name: Option<String>,
+    pub image_export: ImageExport,
+    /// Calculate [QualityMetrics] for nutexb, bntx, and dds outputs.
+    pub report_quality: bool,
+    /// The path for a difference heatmap for nutexb, bntx, and dds outputs.
+    pub heatmap: Option<PathBuf>,
+}
+
+impl ImageFile {
+    /// Save to `output` using the file type and encoding from `settings`.
+    /// This converts the file the same way as each file in a [BatchJob].
+    ///
+    /// Image file types like PNG use the image format from the extension of `output`.
+    pub fn save_converted(
+        &self,
+        output: &Path,
+        settings: OutputSettings,
+        options: &SaveOptions,
+    ) -> Result<ConvertedFile, Error> {
+        save_converted(self, output, settings, options, &BatchProgress::new())
+    }
+}
+
+fn save_converted(
+    image: &ImageFile,
+    output: &Path,
+    settings: OutputSettings,
+    options: &SaveOptions,
+    progress: &BatchProgress,
+) -> Result<ConvertedFile, Error> {
+    let OutputSettings {
+        file_type,
+        format,
+        quality,
+        mipmaps,
+    } = settings;
+
+    let create_parent_folder = || match output.parent() {
+        Some(parent) => std::fs::create_dir_all(parent),
+        None => Ok(()),
+    };
+
+    // Encode all outputs to memory first since cancelling can't interrupt encoding.
+    let name = options
+        .internal_name
+        .clone()
+        .unwrap_or_else(|| image.default_internal_name(output));
+    let mut writer = Cursor::new(Vec::new());
+    match file_type {
+        ImageFileType::Dds => image.write_dds_to(&mut writer, format, quality, mipmaps)?,
+        ImageFileType::Png | ImageFileType::Tiff | ImageFileType::Exr => {
+            let format = image_dds::image::ImageFormat::from_path(output)?;
+            let images = match options.image_export {
+                ImageExport::Base => {
+                    image.write_image_to(&mut writer, format)?;
+                    vec![(output.to_path_buf(), writer.into_inner())]
+                }
+                ImageExport::Layers => {
+                    encode_images(image.slice_images(output, Slices::Layers, format)?, format)?
+                }
+                ImageExport::DepthSlices => {
+                    encode_images(image.slice_images(output, Slices::Depth, format)?, format)?
+                }
+                ImageExport::Channels => {
+                    encode_images(image.channel_images(output, format)?, format)?
+                }
+            };
+
+            progress.check_cancelled()?;
+            create_parent_folder()?;
+            write_outputs(&images)?;
+
+            // Image outputs are lossless.
+            return Ok(ConvertedFile {
+                outputs: images.into_iter().map(|(path, _)| path).collect(),
+                metrics: None,
+            });
+        }
+        ImageFileType::Nutexb => {
+            image.write_nutexb_to(&mut writer, &name, format, quality, mipmaps)?
+        }
+        ImageFileType::Bntx => image.write_bntx_to(&mut writer, &name, format, quality, mipmaps)?,
+    }
+
+    progress.check_cancelled()?;
+    let bytes = writer.into_inner();
+    let metrics = check_quality(image, &bytes, file_type, options)?;
+
+    create_parent_folder()?;
+    let outputs = [(output.to_path_buf(), bytes)];
+    write_outputs(&outputs)?;
+
+    Ok(ConvertedFile {
+        outputs: vec![output.to_path_buf()],
+        metrics,
+    })
+}
+
+// Compare the canonical parent folder and file name since outputs may not exist yet.
+fn normalize_path(path: &Path) -> PathBuf {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let parent = std::fs::canonicalize(parent)
+        .or_else(|_| std::path::absolute(parent))
+        .unwrap_or_else(|_| parent.to_path_buf());
+    let path = parent.join(path.file_name().unwrap_or_default());
+
+    // Windows file systems are case insensitive by default.
+    if cfg!(windows) {
+        PathBuf::from(path.to_string_lossy().to_lowercase())
+    } else {
+        path
+    }
+}
+
+fn encode_images(
+    images: Vec<(PathBuf, DynamicImage)>,
+    format: image_dds::image::ImageFormat,
+) -> Result<Vec<(PathBuf, Vec<u8>)>, Error> {
+    images
+        .into_iter()
+        .map(|(path, image)| {
+            let mut writer = Cursor::new(Vec::new());
+            write_dynamic_image(image, &mut writer, format)?;
+            Ok((path, writer.into_inner()))
+        })
+        .collect()
+}
+
+fn write_outputs(outputs: &[(PathBuf, Vec<u8>)]) -> Result<(), Error> {
+    for (i, (path, bytes)) in outputs.iter().enumerate() {
+        if let Err(e) = std::fs::write(path, bytes) {
+            // Don't leave only some of the outputs for a file.
+            for (path, _) in &outputs[..=i] {
+                let _ = std::fs::remove_file(path);
+            }
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+fn check_quality(
+    image: &ImageFile,
+    bytes: &[u8],
+    file_type: ImageFileType,
+    options: &SaveOptions,
+) -> Result<Option<QualityMetrics>, Error> {
+    if !options.report_quality && options.heatmap.is_none() {
+        return Ok(None);
+    }
+
+    // Decode the encoded file to include any loss from compression.
+    let encoded = ImageFile::from_bytes(bytes, file_type.container())?;
+    if let Some(heatmap) = &options.heatmap {
+        image.save_difference_heatmap(&encoded, heatmap)?;
+    }
+    if options.report_quality {
+        Ok(Some(image.quality_metrics(&encoded)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The extensions for supported files when searching folders with [find_input_files].
+pub const INPUT_EXTENSIONS: &[&str] = &[
+    "nutexb", "bntx", "dds", "png", "tif", "tiff", "exr", "hdr", "jpg", "jpeg", "tga", "bmp",
+];
+
+/// A file found by [find_input_files].
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputFile {
+    pub path: PathBuf,
+    /// The folder containing [InputFile::path] relative to the searched folder.
+    /// This is empty for file paths.
+    pub subfolder: PathBuf,
+}
+
+/// Find the files for `input` sorted by path.
+///
+/// The input can be a file path, a folder to search recursively for files with [INPUT_EXTENSIONS],
+/// or a glob pattern like `fighter/**/*.png`.
+/// Subfolders are relative to the folder or the part of the pattern before the first wildcard.
+pub fn find_input_files(input: &str) -> Vec<InputFile> {
+    let path = Path::new(input);
+    if path.is_dir() {
+        // Unanchored patterns match files in all subfolders.
+        let pattern = format!("*.{{{}}}", INPUT_EXTENSIONS.join(","));
+        find_files(path, &pattern)
+    } else if is_glob_pattern(input) {
+        let (folder, pattern) = split_glob_pattern(path);
+        find_files(&folder, &pattern)
+    } else {
+        vec![InputFile {
+            path: path.to_path_buf(),
+            subfolder: PathBuf::new(),
+        }]
+    }
+}
+
+fn find_files(folder: &Path, pattern: &str) -> Vec<InputFile> {
+    let mut files: Vec<_> = globwalk::GlobWalkerBuilder::from_patterns(folder, &[pattern])
+        .case_insensitive(true)
+        .file_type(globwalk::FileType::FILE)
+        .build()
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| {
+            let path = entry.into_path();
+            let subfolder = path
+                .parent()
+                .and_then(|p| p.strip_prefix(folder).ok())
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            InputFile { path, subfolder }
+        })
+        .collect();
+
+    // The walk order depends on the file system.
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+fn is_glob_pattern(s: &str) -> bool {
+    s.contains(['*', '?', '[', '{'])
+}
+
+// Split a pattern like fighter/*/model/**/*.png into fighter and /*/model/**/*.png.
+fn split_glob_pattern(path: &Path) -> (PathBuf, String) {
+    let mut folder = PathBuf::new();
+    let mut pattern = String::new();
+    for component in path.components() {
+        let component = component.as_os_str().to_string_lossy();
+        if pattern.is_empty() && !is_glob_pattern(&component) {
+            folder.push(component.as_ref());
+        } else {
+            // A leading slash anchors the pattern to the folder like gitignore files.
+            pattern.push('/');
+            pattern.push_str(&component);
+        }
+    }
+
+    if folder.as_os_str().is_empty() {
+        folder.push(".");
+    }
+    (folder, pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch_file(path: &str, file_type: ImageFileType, subfolder: &str) -> BatchFile {
+        BatchFile {
+            path: path.into(),
+            layer_paths: Vec::new(),
+            settings: OutputSettings {
+                file_type,
+                format: ImageFormat::BC7RgbaUnorm,
+                quality: Quality::Fast,
+                mipmaps: Mipmaps::GeneratedAutomatic,
+            },
+            internal_name: None,
+            subfolder: subfolder.into(),
+        }
+    }
+
+    fn batch_job(files: Vec<BatchFile>, output: BatchOutput) -> BatchJob {
+        BatchJob {
+            files,
+            overrides: OutputOverrides::default(),
+            output,
+            image_export: ImageExport::Base,
+            report_quality: false,
+            save_heatmaps: false,
+        }
+    }
+
+    #[test]
+    fn output_conflicts_none() {
+        let job = batch_job(
+            vec![
+                batch_file("a/x.png", ImageFileType::Nutexb, "a"),
+                batch_file("b/x.png", ImageFileType::Nutexb, "b"),
+            ],
+            BatchOutput::Folder("out".into()),
+        );
+        assert!(job.output_conflicts().iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn output_conflicts_same_name() {
+        let job = batch_job(
+            vec![
+                batch_file("a/x.png", ImageFileType::Nutexb, ""),
+                batch_file("a/x.dds", ImageFileType::Nutexb, ""),
+                batch_file("a/y.png", ImageFileType::Nutexb, ""),
+            ],
+            BatchOutput::SameFolder,
+        );
+        let conflicts = job.output_conflicts();
+        assert!(matches!(
+            &conflicts[0],
+            Some(Error::DuplicateOutput { output, other })
+                if output == Path::new("a/x.nutexb") && other == Path::new("a/x.dds")
+        ));
+        assert!(matches!(
+            &conflicts[1],
+            Some(Error::DuplicateOutput { output, other })
+                if output == Path::new("a/x.nutexb") && other == Path::new("a/x.png")
+        ));
+        assert!(conflicts[2].is_none());
+    }
+
+    #[test]
+    fn output_conflicts_same_subfolder() {
+        // Files from different folders with no subfolder have the same output.
+        let job = batch_job(
+            vec![
+                batch_file("a/x.png", ImageFileType::Nutexb, ""),
+                batch_file("b/x.png", ImageFileType::Nutexb, ""),
+            ],
+            BatchOutput::Folder("out".into()),
+        );
+        assert!(
+            job.output_conflicts()
+                .iter()
+                .all(|c| matches!(c, Some(Error::DuplicateOutput { .. })))
+        );
+    }
+
+    #[test]
+    fn output_conflicts_overwrite_input() {
+        let job = batch_job(
+            vec![
+                batch_file("a/x.png", ImageFileType::Png, ""),
+                batch_file("a/y.png", ImageFileType::Nutexb, ""),
+                batch_file("a/y.nutexb", ImageFileType::Dds, ""),
+            ],
+            BatchOutput::SameFolder,
+        );
+        let conflicts = job.output_conflicts();
+        assert!(matches!(
+            &conflicts[0],
+            Some(Error::OutputIsInput { output }) if output == Path::new("a/x.png")
+        ));
+        assert!(matches!(
+            &conflicts[1],
+            Some(Error::OutputIsInput { output }) if output == Path::new("a/y.nutexb")
+        ));
+        assert!(conflicts[2].is_none());
+    }
+
+    #[test]
+    fn output_conflicts_heatmap() {
+        let mut job = batch_job(
+            vec![
+                batch_file("a/x.png", ImageFileType::Nutexb, ""),
+                batch_file("a/x_diff.dds", ImageFileType::Png, ""),
+            ],
+            BatchOutput::Folder("out".into()),
+        );
+        job.save_heatmaps = true;
+        let conflicts = job.output_conflicts();
+        assert!(matches!(
+            &conflicts[0],
+            Some(Error::DuplicateOutput { output, other })
+                if output == Path::new("out/x_diff.png") && other == Path::new("a/x_diff.dds")
+        ));
+        assert!(matches!(
+            &conflicts[1],
+            Some(Error::DuplicateOutput { output, other })
+                if output == Path::new("out/x_diff.png") && other == Path::new("a/x.png")
+        ));
+    }
+
+    #[test]
+    fn output_conflicts_channel_overwrites_input() {
+        let mut job = batch_job(
+            vec![
+                batch_file("a/x.dds", ImageFileType::Png, ""),
+                batch_file("a/x_r.png", ImageFileType::Nutexb, ""),
+            ],
+            BatchOutput::SameFolder,
+        );
+        job.image_export = ImageExport::Channels;
+        let conflicts = job.output_conflicts();
+        assert!(matches!(
+            &conflicts[0],
+            Some(Error::OutputIsInput { output }) if output == Path::new("a/x_r.png")
+        ));
+        assert!(conflicts[1].is_none());
+    }
+
+    #[test]
+    fn output_conflicts_normalized_paths() {
+        let job = batch_job(
+            vec![
+                batch_file("a/./x.png", ImageFileType::Nutexb, ""),
+                batch_file("a/x.dds", ImageFileType::Nutexb, ""),
+            ],
+            BatchOutput::SameFolder,
+        );
+        assert!(
+            job.output_conflicts()
+                .iter()
+                .all(|c| matches!(c, Some(Error::DuplicateOutput { .. })))
+        );
+    }
+
+    #[test]
+    fn split_glob_pattern_folder() {
+        assert_eq!(
+            (PathBuf::from("fighter"), "/*/model/**/*.png".to_string()),
+            split_glob_pattern(Path::new("fighter/*/model/**/*.png"))
+        );
+    }
+
+    #[test]
+    fn split_glob_pattern_nested_folder() {
+        assert_eq!(
+            (PathBuf::from("fighter/mario"), "/*.{png,dds}".to_string()),
+            split_glob_pattern(Path::new("fighter/mario/*.{png,dds}"))
+        );
+    }
+
+    #[test]
+    fn split_glob_pattern_no_folder() {
+        assert_eq!(
+            (PathBuf::from("."), "/*.png".to_string()),
+            split_glob_pattern(Path::new("*.png"))
+        );
+    }
+
+    #[test]
+    fn split_glob_pattern_wildcard_in_name() {
+        assert_eq!(
+            (PathBuf::from("model/body"), "/def_*_col.png".to_string()),
+            split_glob_pattern(Path::new("model/body/def_*_col.png"))
+        );
+    }
+
+    #[test]
+    fn is_glob_pattern_special_characters() {
+        assert!(is_glob_pattern("*.png"));
+        assert!(is_glob_pattern("eye_?.png"));
+        assert!(is_glob_pattern("eye_[01].png"));
+        assert!(is_glob_pattern("*.{png,dds}"));
+        assert!(!is_glob_pattern("fighter/mario/eye.png"));
+    }
+
+    #[test]
+    fn find_input_files_folder_and_pattern() {
+        let root = std::env::temp_dir().join("ultimate_tex_find_input_files");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        for file in ["a/x.png", "a/b/y.PNG", "a/b/z.dds", "a/notes.txt"] {
+            std::fs::write(root.join(file), []).unwrap();
+        }
+
+        // Folders find supported files in all subfolders.
+        let files = find_input_files(root.to_str().unwrap());
+        let mut found: Vec<_> = files
+            .iter()
+            .map(|f| {
+                (
+                    f.path.strip_prefix(&root).unwrap().to_path_buf(),
+                    f.subfolder.clone(),
+                )
+            })
+            .collect();
+        found.sort();
+        assert_eq!(
+            vec![
+                (PathBuf::from("a/b/y.PNG"), PathBuf::from("a/b")),
+                (PathBuf::from("a/b/z.dds"), PathBuf::from("a/b")),
+                (PathBuf::from("a/x.png"), PathBuf::from("a")),
+            ],
+            found
+        );
+
+        // Patterns are anchored to the folder before the first wildcard.
+        let pattern = root.join("a/*.png");
+        let files = find_input_files(pattern.to_str().unwrap());
+        assert_eq!(
+            vec![InputFile {
+                path: root.join("a/x.png"),
+                subfolder: PathBuf::new(),
+            }],
+            files
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn save_converted_cancelled_layers() {
+        let root = std::env::temp_dir().join("ultimate_tex_save_converted_cancelled");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let image = ImageFile::Image(image_dds::SurfaceRgba8 {
+            width: 1,
+            height: 1,
+            depth: 1,
+            layers: 2,
+            mipmaps: 1,
+            data: vec![0; 8],
+        });
+        let settings = OutputSettings {
+            file_type: ImageFileType::Png,
+            format: ImageFormat::Rgba8Unorm,
+            quality: Quality::Fast,
+            mipmaps: Mipmaps::Disabled,
+        };
+        let options = SaveOptions {
+            image_export: ImageExport::Layers,
+            ..Default::default()
+        };
+
+        // Cancelling after encoding shouldn't save any of the layers.
+        let progress = BatchProgress::new();
+        progress.cancel();
+        let result = save_converted(&image, &root.join("a.png"), settings, &options, &progress);
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert!(!root.exists());
+
+        let result = save_converted(
+            &image,
+            &root.join("a.png"),
+            settings,
+            &options,
+            &BatchProgress::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![root.join("a_0.png"), root.join("a_1.png")],
+            result.outputs
+        );
+        assert!(result.outputs.iter().all(|p| p.exists()));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn find_input_files_file_path() {
+        assert_eq!(
+            vec![InputFile {
+                path: PathBuf::from("missing/x.png"),
+                subfolder: PathBuf::new(),
+            }],
+            find_input_files("missing/x.png")
+        );
+    }
+}
@@ -0,0 +1,563 @@
+use std::path::Path;
+
+use image_dds::{ImageFormat, Mipmaps, Quality, Surface};
+use tegra_swizzle::{
+    BlockHeight,
+    surface::{BlockDim, deswizzle_surface, swizzle_surface},
+};
+
+use crate::{Container, Error, ImageFile};
+
+// The NX header starts after the 0x20 byte BNTX header.
+const TEXTURE_COUNT_OFFSET: usize = 0x24;
+const TEXTURE_INFOS_OFFSET: usize = 0x28;
+
+// Offsets relative to the start of each BRTI texture info.
+const TILE_MODE_OFFSET: usize = 0x12;
+const MIPMAP_COUNT_OFFSET: usize = 0x16;
+const FORMAT_OFFSET: usize = 0x1C;
+const WIDTH_OFFSET: usize = 0x24;
+const HEIGHT_OFFSET: usize = 0x28;
+const DEPTH_OFFSET: usize = 0x2C;
+const LAYER_COUNT_OFFSET: usize = 0x30;
+const BLOCK_HEIGHT_LOG2_OFFSET: usize = 0x34;
+const IMAGE_SIZE_OFFSET: usize = 0x50;
+const NAME_OFFSET: usize = 0x60;
+const MIPMAP_OFFSETS_OFFSET: usize = 0x70;
+
+const TILE_MODE_LINEAR: u16 = 1;
+
+/// A BNTX file with one or more textures like UI archives.
+///
+/// [ImageFile::Bntx] only converts the first texture.
+/// The archive keeps the original file data, so replacing a texture
+/// only changes the image data for that texture and leaves the other textures untouched.
+#[derive(Debug, Clone)]
+pub struct BntxArchive {
+    data: Vec<u8>,
+    textures: Vec<BntxTexture>,
+}
+
+/// The properties of a single texture in a [BntxArchive].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BntxTexture {
+    pub name: String,
+    /// The image format or `None` if the format is not supported.
+    pub format: Option<ImageFormat>,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub layer_count: u32,
+    pub mipmap_count: u32,
+    format_code: u32,
+    is_linear: bool,
+    block_height_log2: u32,
+    data_offset: usize,
+    /// The size in bytes of the image data for all layers and mipmaps.
+    pub data_size: usize,
+}
+
+impl BntxArchive {
+    pub fn from_file<P: AsRef<Path>>(input: P) -> Result<Self, Error> {
+        Self::from_bytes(std::fs::read(input)?)
+    }
+
+    /// Read the textures from the bytes of a BNTX file.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Error> {
+        let textures = read_textures(&data)?;
+        Ok(Self { data, textures })
+    }
+
+    /// The textures in the order they are stored in the file.
+    pub fn textures(&self) -> &[BntxTexture] {
+        &self.textures
+    }
+
+    /// The file data including any replaced textures.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn save(&self, output: &Path) -> Result<(), Error> {
+        std::fs::write(output, &self.data)?;
+        Ok(())
+    }
+
+    /// Convert the texture with the given `name` to a DDS file without changing the image data.
+    pub fn texture_image(&self, name: &str) -> Result<ImageFile, Error> {
+        let texture = self.texture(name)?;
+        let image_format = texture.image_format()?;
+        let block_height = texture.block_height()?;
+
+        let swizzled = &self.data[texture.data_offset..texture.data_offset + texture.data_size];
+        let data = deswizzle_surface(
+            texture.width,
+            texture.height,
+            texture.depth,
+            swizzled,
+            block_dim(image_format),
+            Some(block_height),
+            bytes_per_block(image_format),
+            texture.mipmap_count,
+            texture.layer_count,
+        )
+        .map_err(Error::invalid_container(Container::Bntx))?;
+
+        let dds = Surface {
+            width: texture.width,
+            height: texture.height,
+            depth: texture.depth,
+            layers: texture.layer_count,
+            mipmaps: texture.mipmap_count,
+            image_format,
+            data,
+        }
+        .to_dds()
+        .map_err(Error::encode)?;
+        Ok(ImageFile::Dds(dds))
+    }
+
+    /// Replace the image data for the texture with the given `name`.
+    ///
+    /// The replacement `image` must have the same dimensions and layers as the texture.
+    /// The image is encoded using the format and mipmap count of the existing texture,
+    /// so the file layout and the other textures don't change.
+    pub fn replace_texture(
+        &mut self,
+        name: &str,
+        image: &ImageFile,
+        quality: Quality,
+    ) -> Result<(), Error> {
+        let texture = self.texture(name)?.clone();
+        let image_format = texture.image_format()?;
+        let block_height = texture.block_height()?;
+
+        let expected = (texture.width, texture.height, texture.depth);
+        let actual = image.dimensions();
+        if actual != expected {
+            return Err(Error::TextureDimensions {
+                name: name.to_string(),
+                actual,
+                expected,
+            });
+        }
+        if image.layer_count() != texture.layer_count {
+            return Err(Error::TextureLayers {
+                name: name.to_string(),
+                actual: image.layer_count(),
+                expected: texture.layer_count,
+            });
+        }
+
+        let dds = image.encode(
+            image_format,
+            quality,
+            Mipmaps::GeneratedExactly(texture.mipmap_count),
+        )?;
+        let surface = Surface::from_dds(&dds).map_err(Error::decode)?;
+        let swizzled = swizzle_surface(
+            texture.width,
+            texture.height,
+            texture.depth,
+            surface.data,
+            block_dim(image_format),
+            Some(block_height),
+            bytes_per_block(image_format),
+            texture.mipmap_count,
+            texture.layer_count,
+        )
+        .map_err(Error::encode)?;
+
+        if swizzled.len() > texture.data_size {
+            return Err(invalid(format!(
+                "image data for {name:?} does not fit in the original {} bytes",
+                texture.data_size
+            )));
+        }
+
+        // Fill any remaining space with zeros to not leave old image data.
+        let start = texture.data_offset;
+        let end = start + texture.data_size;
+        self.data[start..start + swizzled.len()].copy_from_slice(&swizzled);
+        self.data[start + swizzled.len()..end].fill(0);
+        Ok(())
+    }
+
+    fn texture(&self, name: &str) -> Result<&BntxTexture, Error> {
+        self.textures
+            .iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| Error::MissingTexture {
+                name: name.to_string(),
+            })
+    }
+}
+
+impl BntxTexture {
+    pub(crate) fn image_format(&self) -> Result<ImageFormat, Error> {
+        if self.is_linear {
+            return Err(Error::UnsupportedFormat {
+                container: Container::Bntx,
+                reason: format!("texture {:?} uses linear tiling", self.name),
+            });
+        }
+        self.format.ok_or_else(|| Error::UnsupportedFormat {
+            container: Container::Bntx,
+            reason: format!("{:#06X} for texture {:?}", self.format_code, self.name),
+        })
+    }
+
+    fn block_height(&self) -> Result<BlockHeight, Error> {
+        BlockHeight::new(1 << self.block_height_log2)
+            .ok_or_else(|| invalid(format!("invalid block height for {:?}", self.name)))
+    }
+}
+
+// Read the texture headers without deswizzling any image data.
+fn read_textures(data: &[u8]) -> Result<Vec<BntxTexture>, Error> {
+    if !data.starts_with(b"BNTX") {
+        return Err(invalid("missing BNTX magic"));
+    }
+
+    let count = read_u32(data, TEXTURE_COUNT_OFFSET)?;
+    let infos = read_offset(data, TEXTURE_INFOS_OFFSET)?;
+    (0..count as usize)
+        .map(|i| {
+            let offset = i
+                .checked_mul(8)
+                .and_then(|offset| infos.checked_add(offset))
+                .ok_or_else(|| invalid("texture info offsets are out of bounds"))?;
+            read_texture(data, read_offset(data, offset)?)
+        })
+        .collect()
+}
+
+// Match ImageFile::Bntx by only using the first texture.
+pub(crate) fn read_first_texture(data: &[u8]) -> Result<BntxTexture, Error> {
+    read_textures(data)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| invalid("file has no textures"))
+}
+
+fn read_texture(data: &[u8], info: usize) -> Result<BntxTexture, Error> {
+    if read_bytes(data, info, 4)? != b"BRTI" {
+        return Err(invalid("missing BRTI magic"));
+    }
+
+    let field = |offset: usize| {
+        info.checked_add(offset)
+            .ok_or_else(|| invalid(format!("texture info at {info:#X} is out of bounds")))
+    };
+
+    let format_code = read_u32(data, field(FORMAT_OFFSET)?)?;
+    let texture = BntxTexture {
+        name: read_name(data, read_offset(data, field(NAME_OFFSET)?)?)?,
+        format: image_format(format_code),
+        width: read_u32(data, field(WIDTH_OFFSET)?)?,
+        height: read_u32(data, field(HEIGHT_OFFSET)?)?,
+        depth: read_u32(data, field(DEPTH_OFFSET)?)?.max(1),
+        layer_count: read_u32(data, field(LAYER_COUNT_OFFSET)?)?.max(1),
+        mipmap_count: u32::from(read_u16(data, field(MIPMAP_COUNT_OFFSET)?)?).max(1),
+        format_code,
+        is_linear: read_u16(data, field(TILE_MODE_OFFSET)?)? == TILE_MODE_LINEAR,
+        // Only the low 3 bits of the texture layout store the block height.
+        block_height_log2: read_u32(data, field(BLOCK_HEIGHT_LOG2_OFFSET)?)? & 0x7,
+        // The mipmaps and layers are stored together starting from the first mipmap.
+        data_offset: read_offset(data, read_offset(data, field(MIPMAP_OFFSETS_OFFSET)?)?)?,
+        data_size: read_u32(data, field(IMAGE_SIZE_OFFSET)?)? as usize,
+    };
+
+    if texture
+        .data_offset
+        .checked_add(texture.data_size)
+        .is_none_or(|end| end > data.len())
+    {
+        return Err(invalid(format!(
+            "image data for {:?} is out of bounds",
+            texture.name
+        )));
+    }
+    Ok(texture)
+}
+
+fn read_name(data: &[u8], offset: usize) -> Result<String, Error> {
+    // Names are stored in the string table with a u16 length prefix.
+    let length = read_u16(data, offset)? as usize;
+    let start = offset
+        .checked_add(2)
+        .ok_or_else(|| invalid(format!("unexpected end of file at {offset:#X}")))?;
+    let bytes = read_bytes(data, start, length)?;
+    Ok(String::from_utf8_lossy(bytes).to_string())
+}
+
+fn image_format(format_code: u32) -> Option<ImageFormat> {
+    // The high byte is the channel layout, and the low byte is the data type.
+    match format_code {
+        0x0201 => Some(ImageFormat::R8Unorm),
+        0x0b01 => Some(ImageFormat::Rgba8Unorm),
+        0x0b06 => Some(ImageFormat::Rgba8UnormSrgb),
+        0x0c01 => Some(ImageFormat::Bgra8Unorm),
+        0x0c06 => Some(ImageFormat::Bgra8UnormSrgb),
+        0x1a01 => Some(ImageFormat::BC1RgbaUnorm),
+        0x1a06 => Some(ImageFormat::BC1RgbaUnormSrgb),
+        0x1b01 => Some(ImageFormat::BC2RgbaUnorm),
+        0x1b06 => Some(ImageFormat::BC2RgbaUnormSrgb),
+        0x1c01 => Some(ImageFormat::BC3RgbaUnorm),
+        0x1c06 => Some(ImageFormat::BC3RgbaUnormSrgb),
+        0x1d01 => Some(ImageFormat::BC4RUnorm),
+        0x1d02 => Some(ImageFormat::BC4RSnorm),
+        0x1e01 => Some(ImageFormat::BC5RgUnorm),
+        0x1e02 => Some(ImageFormat::BC5RgSnorm),
+        0x1f05 => Some(ImageFormat::BC6hRgbSfloat),
+        0x1f0a => Some(ImageFormat::BC6hRgbUfloat),
+        0x2001 => Some(ImageFormat::BC7RgbaUnorm),
+        0x2006 => Some(ImageFormat::BC7RgbaUnormSrgb),
+        _ => None,
+    }
+}
+
+fn block_dim(format: ImageFormat) -> BlockDim {
+    match format {
+        ImageFormat::R8Unorm
+        | ImageFormat::Rgba8Unorm
+        | ImageFormat::Rgba8UnormSrgb
+        | ImageFormat::Bgra8Unorm
+        | ImageFormat::Bgra8UnormSrgb => BlockDim::uncompressed(),
+        _ => BlockDim::block_4x4(),
+    }
+}
+
+// The size of a pixel for uncompressed formats or a 4x4 block for compressed formats.
+fn bytes_per_block(format: ImageFormat) -> u32 {
+    match format {
+        ImageFormat::R8Unorm => 1,
+        ImageFormat::Rgba8Unorm
+        | ImageFormat::Rgba8UnormSrgb
+        | ImageFormat::Bgra8Unorm
+        | ImageFormat::Bgra8UnormSrgb => 4,
+        ImageFormat::BC1RgbaUnorm
+        | ImageFormat::BC1RgbaUnormSrgb
+        | ImageFormat::BC4RUnorm
+        | ImageFormat::BC4RSnorm => 8,
+        _ => 16,
+    }
+}
+
+fn read_bytes(data: &[u8], offset: usize, length: usize) -> Result<&[u8], Error> {
+    offset
+        .checked_add(length)
+        .and_then(|end| data.get(offset..end))
+        .ok_or_else(|| invalid(format!("unexpected end of file at {offset:#X}")))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, Error> {
+    let bytes = read_bytes(data, offset, 2)?;
+    Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Error> {
+    let bytes = read_bytes(data, offset, 4)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+// Offsets are stored as u64 values relative to the start of the file.
+fn read_offset(data: &[u8], offset: usize) -> Result<usize, Error> {
+    let bytes = read_bytes(data, offset, 8)?;
+    usize::try_from(u64::from_le_bytes(bytes.try_into().unwrap()))
+        .map_err(Error::invalid_container(Container::Bntx))
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::InvalidContainer {
+        container: Container::Bntx,
+        reason: reason.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image_dds::SurfaceRgba8;
+
+    const TEXTURE_INFO_SIZE: usize = 0x80;
+
+    fn pixels(value: u8) -> Vec<u8> {
+        (0..4 * 4 * 4).map(|i| value.wrapping_add(i)).collect()
+    }
+
+    // Build a file with an uncompressed 4x4 texture for each name.
+    fn archive_bytes(textures: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        fn write(data: &mut Vec<u8>, offset: usize, bytes: &[u8]) {
+            if data.len() < offset + bytes.len() {
+                data.resize(offset + bytes.len(), 0);
+            }
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        let mut data = b"BNTX".to_vec();
+        data.resize(0x40, 0);
+        write(&mut data, 0x20, b"NX  ");
+        write(
+            &mut data,
+            TEXTURE_COUNT_OFFSET,
+            &(textures.len() as u32).to_le_bytes(),
+        );
+        write(&mut data, TEXTURE_INFOS_OFFSET, &0x40u64.to_le_bytes());
+
+        let infos = 0x40 + textures.len() * 8;
+        for (i, (name, pixels)) in textures.iter().enumerate() {
+            let swizzled = swizzle_surface(
+                4,
+                4,
+                1,
+                pixels,
+                BlockDim::uncompressed(),
+                BlockHeight::new(1),
+                4,
+                1,
+                1,
+            )
+            .unwrap();
+
+            let info = infos + i * TEXTURE_INFO_SIZE;
+            write(&mut data, 0x40 + i * 8, &(info as u64).to_le_bytes());
+            write(&mut data, info, b"BRTI");
+            write(&mut data, info + MIPMAP_COUNT_OFFSET, &1u16.to_le_bytes());
+            write(&mut data, info + FORMAT_OFFSET, &0x0b01u32.to_le_bytes());
+            write(&mut data, info + WIDTH_OFFSET, &4u32.to_le_bytes());
+            write(&mut data, info + HEIGHT_OFFSET, &4u32.to_le_bytes());
+            write(&mut data, info + DEPTH_OFFSET, &1u32.to_le_bytes());
+            write(&mut data, info + LAYER_COUNT_OFFSET, &1u32.to_le_bytes());
+            let image_size = swizzled.len() as u32;
+            write(
+                &mut data,
+                info + IMAGE_SIZE_OFFSET,
+                &image_size.to_le_bytes(),
+            );
+
+            let name_offset = data.len().max(infos + textures.len() * TEXTURE_INFO_SIZE);
+            write(
+                &mut data,
+                info + NAME_OFFSET,
+                &(name_offset as u64).to_le_bytes(),
+            );
+            write(&mut data, name_offset, &(name.len() as u16).to_le_bytes());
+            write(&mut data, name_offset + 2, name.as_bytes());
+
+            let mipmap_offsets = data.len();
+            let mipmap_offsets_offset = info + MIPMAP_OFFSETS_OFFSET;
+            write(
+                &mut data,
+                mipmap_offsets_offset,
+                &(mipmap_offsets as u64).to_le_bytes(),
+            );
+            let data_offset = mipmap_offsets + 8;
+            write(
+                &mut data,
+                mipmap_offsets,
+                &(data_offset as u64).to_le_bytes(),
+            );
+            write(&mut data, data_offset, &swizzled);
+        }
+        data
+    }
+
+    fn image(pixels: Vec<u8>, width: u32) -> ImageFile {
+        ImageFile::Image(SurfaceRgba8 {
+            width,
+            height: 4,
+            depth: 1,
+            layers: 1,
+            mipmaps: 1,
+            data: pixels,
+        })
+    }
+
+    #[test]
+    fn list_textures() {
+        let bytes = archive_bytes(&[("a", pixels(0)), ("bb", pixels(1))]);
+        let archive = BntxArchive::from_bytes(bytes).unwrap();
+        let names: Vec<_> = archive.textures().iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(vec!["a", "bb"], names);
+
+        let texture = &archive.textures()[1];
+        assert_eq!(Some(ImageFormat::Rgba8Unorm), texture.format);
+        assert_eq!((4, 4, 1), (texture.width, texture.height, texture.depth));
+        assert_eq!((1, 1), (texture.layer_count, texture.mipmap_count));
+    }
+
+    #[test]
+    fn texture_image() {
+        let bytes = archive_bytes(&[("a", pixels(0)), ("b", pixels(100))]);
+        let archive = BntxArchive::from_bytes(bytes).unwrap();
+        let image = archive.texture_image("b").unwrap();
+        assert_eq!(pixels(100), image.to_image().unwrap().into_raw());
+    }
+
+    #[test]
+    fn replace_texture_leaves_other_textures() {
+        let bytes = archive_bytes(&[("a", pixels(0)), ("b", pixels(100))]);
+        let mut archive = BntxArchive::from_bytes(bytes.clone()).unwrap();
+        archive
+            .replace_texture("a", &image(pixels(50), 4), Quality::Fast)
+            .unwrap();
+
+        assert_eq!(bytes.len(), archive.as_bytes().len());
+        let a = archive.texture_image("a").unwrap();
+        assert_eq!(pixels(50), a.to_image().unwrap().into_raw());
+        let b = archive.texture_image("b").unwrap();
+        assert_eq!(pixels(100), b.to_image().unwrap().into_raw());
+    }
+
+    #[test]
+    fn replace_missing_texture() {
+        let bytes = archive_bytes(&[("a", pixels(0))]);
+        let mut archive = BntxArchive::from_bytes(bytes).unwrap();
+        let result = archive.replace_texture("b", &image(pixels(50), 4), Quality::Fast);
+        assert!(matches!(result, Err(Error::MissingTexture { name }) if name == "b"));
+    }
+
+    #[test]
+    fn replace_texture_dimensions() {
+        let bytes = archive_bytes(&[("a", pixels(0))]);
+        let mut archive = BntxArchive::from_bytes(bytes).unwrap();
+        let pixels = vec![0u8; 8 * 4 * 4];
+        let result = archive.replace_texture("a", &image(pixels, 8), Quality::Fast);
+        assert!(matches!(
+            result,
+            Err(Error::TextureDimensions {
+                actual: (8, 4, 1),
+                expected: (4, 4, 1),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn invalid_magic() {
+        let result = BntxArchive::from_bytes(b"DDS ".to_vec());
+        assert!(matches!(result, Err(Error::InvalidContainer { .. })));
+    }
+
+    #[test]
+    fn texture_info_offset_overflow() {
+        let mut bytes = archive_bytes(&[("a", pixels(0))]);
+        bytes[0x40..0x48].copy_from_slice(&(usize::MAX as u64).to_le_bytes());
+        let result = BntxArchive::from_bytes(bytes);
+        assert!(matches!(result, Err(Error::InvalidContainer { .. })));
+    }
+
+    #[test]
+    fn block_height_flags() {
+        // Bits above the block height should not affect the swizzling.
+        let mut bytes = archive_bytes(&[("a", pixels(0))]);
+        let offset = read_offset(&bytes, 0x40).unwrap() + BLOCK_HEIGHT_LOG2_OFFSET;
+        let layout = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        bytes[offset..offset + 4].copy_from_slice(&(layout | 0x10000).to_le_bytes());
+
+        let archive = BntxArchive::from_bytes(bytes).unwrap();
+        assert!(archive.texture_image("a").is_ok());
+    }
+}
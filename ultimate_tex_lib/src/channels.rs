@@ -0,0 +1,390 @@
+use std::path::{Path, PathBuf};
+
+use image_dds::{
+    SurfaceRgba8, SurfaceRgba32Float,
+    image::{
+        DynamicImage, GrayImage, ImageBuffer, ImageFormat, Luma, Pixel, Rgb, Rgb32FImage,
+        Rgba32FImage,
+    },
+};
+use strum::IntoEnumIterator;
+
+use crate::{Error, ImageFile, channel_path, is_high_precision, save_to_file, write_dynamic_image};
+
+/// An RGBA color channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumIter, strum::EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum Channel {
+    R,
+    G,
+    B,
+    A,
+}
+
+impl Channel {
+    /// The index of the channel in RGBA order.
+    pub fn index(&self) -> usize {
+        match self {
+            Channel::R => 0,
+            Channel::G => 1,
+            Channel::B => 2,
+            Channel::A => 3,
+        }
+    }
+}
+
+/// The values for a single channel when packing channels with [pack_channels].
+#[derive(Debug, Clone)]
+pub enum ChannelSource {
+    /// A channel from an image.
+    /// Grayscale images have the same value for the R, G, and B channels.
+    Image {
+        image: DynamicImage,
+        channel: Channel,
+    },
+    /// The same value in the range `0.0` to `1.0` for all pixels.
+    Constant(f32),
+}
+
+/// The source for an output channel when packing channels with [pack_channels].
+#[derive(Debug, Clone)]
+pub struct PackChannel {
+    pub source: ChannelSource,
+    /// Use `1.0 - value` like when converting smoothness to roughness.
+    pub invert: bool,
+}
+
+impl PackChannel {
+    pub fn constant(value: f32) -> Self {
+        Self {
+            source: ChannelSource::Constant(value),
+            invert: false,
+        }
+    }
+}
+
+/// Combine channels from separate images or constant values into a single RGBA image.
+/// This is useful for packed textures like PRM maps with metalness, roughness,
+/// ambient occlusion, and specular in the RGBA channels.
+///
+/// All images must have the same dimensions.
+/// Textures with only constant channels use the minimum 4x4 size for block compressed formats.
+pub fn pack_channels(channels: [PackChannel; 4]) -> Result<ImageFile, Error> {
+    let images: Vec<_> = channels
+        .iter()
+        .map(|c| match &c.source {
+            ChannelSource::Image { image, .. } => Some(image),
+            ChannelSource::Constant(_) => None,
+        })
+        .collect();
+
+    let (width, height) = images
+        .iter()
+        .flatten()
+        .map(|i| (i.width(), i.height()))
+        .next()
+        .unwrap_or((4, 4));
+    for (channel, image) in images.iter().enumerate() {
+        let Some(image) = image else {
+            continue;
+        };
+        if (image.width(), image.height()) != (width, height) {
+            return Err(Error::ChannelDimensions {
+                channel,
+                actual: (image.width(), image.height()),
+                expected: (width, height),
+            });
+        }
+    }
+
+    // Avoid losing precision if any of the sources use more than 8 bits per channel.
+    let is_float = images.iter().flatten().any(|i| is_high_precision(i));
+
+    let mut packed = Rgba32FImage::new(width, height);
+    for (i, channel) in channels.iter().enumerate() {
+        match &channel.source {
+            ChannelSource::Image { image, channel: c } => {
+                let source = image.to_rgba32f();
+                for (out, pixel) in packed.pixels_mut().zip(source.pixels()) {
+                    out[i] = pixel[c.index()];
+                }
+            }
+            ChannelSource::Constant(value) => {
+                for out in packed.pixels_mut() {
+                    out[i] = *value;
+                }
+            }
+        }
+
+        if channel.invert {
+            for out in packed.pixels_mut() {
+                out[i] = 1.0 - out[i];
+            }
+        }
+    }
+
+    if is_float {
+        Ok(ImageFile::ImageFloat(SurfaceRgba32Float {
+            width,
+            height,
+            depth: 1,
+            layers: 1,
+            mipmaps: 1,
+            data: packed.into_raw(),
+        }))
+    } else {
+        Ok(ImageFile::Image(SurfaceRgba8 {
+            width,
+            height,
+            depth: 1,
+            layers: 1,
+            mipmaps: 1,
+            data: DynamicImage::ImageRgba32F(packed).to_rgba8().into_raw(),
+        }))
+    }
+}
+
+impl ImageFile {
+    /// Decode a single channel of the base mipmap as a grayscale image.
+    pub fn to_channel_image(&self, channel: Channel) -> Result<GrayImage, Error> {
+        let image = self.to_image()?;
+        Ok(extract_channel(&image, channel, |v| Luma([v])))
+    }
+
+    /// Save each RGBA channel of the base mipmap to a separate grayscale image
+    /// and return the saved paths.
+    ///
+    /// The channel is added to the file name like `def_mario_001_prm_r.png`.
+    /// PNG and TIFF files use 16-bit channels for floating point formats.
+    pub fn save_channel_images(&self, output: &Path) -> Result<Vec<PathBuf>, Error> {
+        let format = ImageFormat::from_path(output)?;
+        self.channel_images(output, format)?
+            .into_iter()
+            .map(|(path, image)| {
+                save_to_file(&path, |writer| write_dynamic_image(image, writer, format))?;
+                Ok(path)
+            })
+            .collect()
+    }
+
+    // Split the channels into images without saving to separate the conversion from file IO.
+    pub(crate) fn channel_images(
+        &self,
+        output: &Path,
+        format: ImageFormat,
+    ) -> Result<Vec<(PathBuf, DynamicImage)>, Error> {
+        let images: Vec<DynamicImage> = if self.needs_float_image(format) {
+            let image = self.to_image_f32()?;
+            match format {
+                // Preserve HDR values since these formats don't support grayscale.
+                ImageFormat::OpenExr | ImageFormat::Hdr => Channel::iter()
+                    .map(|c| {
+                        let gray: Rgb32FImage = extract_channel(&image, c, |v| Rgb([v, v, v]));
+                        gray.into()
+                    })
+                    .collect(),
+                _ => Channel::iter()
+                    .map(|c| {
+                        let to_u16 = |v: f32| (v.clamp(0.0, 1.0) * 65535.0).round() as u16;
+                        let gray: ImageBuffer<Luma<u16>, _> =
+                            extract_channel(&image, c, |v| Luma([to_u16(v)]));
+                        gray.into()
+                    })
+                    .collect(),
+            }
+        } else {
+            let image = self.to_image()?;
+            Channel::iter()
+                .map(|c| extract_channel(&image, c, |v| Luma([v])).into())
+                .collect()
+        };
+
+        Ok(Channel::iter()
+            .map(|channel| channel_path(output, channel))
+            .zip(images)
+            .collect())
+    }
+}
+
+fn extract_channel<P, Q, F>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    channel: Channel,
+    to_pixel: F,
+) -> ImageBuffer<Q, Vec<Q::Subpixel>>
+where
+    P: Pixel,
+    Q: Pixel,
+    F: Fn(P::Subpixel) -> Q,
+{
+    ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        to_pixel(image.get_pixel(x, y).channels()[channel.index()])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image_dds::image::{Rgba, RgbaImage};
+
+    fn image_channel(image: RgbaImage, channel: Channel) -> PackChannel {
+        PackChannel {
+            source: ChannelSource::Image {
+                image: image.into(),
+                channel,
+            },
+            invert: false,
+        }
+    }
+
+    fn rgba8_data(image: ImageFile) -> Vec<u8> {
+        match image {
+            ImageFile::Image(surface) => surface.data,
+            _ => panic!("expected an Rgba8 image"),
+        }
+    }
+
+    #[test]
+    fn pack_channels_routing() {
+        let a = RgbaImage::from_pixel(2, 1, Rgba([10, 20, 30, 40]));
+        let b = RgbaImage::from_pixel(2, 1, Rgba([50, 60, 70, 80]));
+        let packed = pack_channels([
+            image_channel(b.clone(), Channel::A),
+            image_channel(a.clone(), Channel::R),
+            image_channel(b, Channel::G),
+            image_channel(a, Channel::B),
+        ])
+        .unwrap();
+        assert_eq!(vec![80, 10, 60, 30, 80, 10, 60, 30], rgba8_data(packed));
+    }
+
+    #[test]
+    fn pack_channels_constant() {
+        let image = RgbaImage::from_pixel(3, 2, Rgba([255, 0, 0, 0]));
+        let packed = pack_channels([
+            image_channel(image, Channel::R),
+            PackChannel::constant(0.0),
+            PackChannel::constant(1.0),
+            PackChannel {
+                source: ChannelSource::Constant(1.0),
+                invert: true,
+            },
+        ])
+        .unwrap();
+        assert_eq!((3, 2, 1), packed.dimensions());
+        assert_eq!([255, 0, 255, 0].repeat(6), rgba8_data(packed));
+    }
+
+    #[test]
+    fn pack_channels_only_constants() {
+        let packed = pack_channels([
+            PackChannel::constant(0.0),
+            PackChannel::constant(0.0),
+            PackChannel::constant(0.0),
+            PackChannel::constant(1.0),
+        ])
+        .unwrap();
+        assert_eq!((4, 4, 1), packed.dimensions());
+        assert_eq!([0, 0, 0, 255].repeat(16), rgba8_data(packed));
+    }
+
+    #[test]
+    fn pack_channels_invert() {
+        let image = RgbaImage::from_pixel(1, 1, Rgba([0, 64, 0, 0]));
+        let mut roughness = image_channel(image, Channel::G);
+        roughness.invert = true;
+        let packed = pack_channels([
+            PackChannel::constant(0.0),
+            roughness,
+            PackChannel::constant(0.0),
+            PackChannel::constant(0.0),
+        ])
+        .unwrap();
+        assert_eq!(vec![0, 191, 0, 0], rgba8_data(packed));
+    }
+
+    #[test]
+    fn channel_images_rgba8() {
+        let image = ImageFile::Image(SurfaceRgba8 {
+            width: 2,
+            height: 1,
+            depth: 1,
+            layers: 1,
+            mipmaps: 1,
+            data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+        });
+        let images = image
+            .channel_images(Path::new("textures/prm.png"), ImageFormat::Png)
+            .unwrap();
+
+        let paths: Vec<_> = images.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(
+            vec![
+                PathBuf::from("textures/prm_r.png"),
+                PathBuf::from("textures/prm_g.png"),
+                PathBuf::from("textures/prm_b.png"),
+                PathBuf::from("textures/prm_a.png"),
+            ],
+            paths
+        );
+
+        let values: Vec<_> = images
+            .into_iter()
+            .map(|(_, image)| image.into_luma8().into_raw())
+            .collect();
+        assert_eq!(vec![vec![1, 5], vec![2, 6], vec![3, 7], vec![4, 8]], values);
+    }
+
+    #[test]
+    fn channel_images_float() {
+        let image = ImageFile::ImageFloat(SurfaceRgba32Float {
+            width: 1,
+            height: 1,
+            depth: 1,
+            layers: 1,
+            mipmaps: 1,
+            data: vec![0.0, 0.5, 1.0, 2.0],
+        });
+
+        // EXR preserves HDR values in RGB images.
+        let images = image
+            .channel_images(Path::new("lut.exr"), ImageFormat::OpenExr)
+            .unwrap();
+        let values: Vec<_> = images
+            .into_iter()
+            .map(|(_, image)| image.into_rgb32f().into_raw())
+            .collect();
+        assert_eq!(
+            vec![vec![0.0; 3], vec![0.5; 3], vec![1.0; 3], vec![2.0; 3]],
+            values
+        );
+
+        // PNG uses 16-bit grayscale clamped to the 0.0 to 1.0 range.
+        let images = image
+            .channel_images(Path::new("lut.png"), ImageFormat::Png)
+            .unwrap();
+        let values: Vec<_> = images
+            .into_iter()
+            .map(|(_, image)| image.into_luma16().into_raw())
+            .collect();
+        assert_eq!(vec![vec![0], vec![32768], vec![65535], vec![65535]], values);
+    }
+
+    #[test]
+    fn pack_channels_mismatched_dimensions() {
+        let result = pack_channels([
+            image_channel(RgbaImage::new(4, 4), Channel::R),
+            PackChannel::constant(0.0),
+            image_channel(RgbaImage::new(8, 4), Channel::B),
+            PackChannel::constant(1.0),
+        ]);
+        assert!(matches!(
+            result,
+            Err(Error::ChannelDimensions {
+                channel: 2,
+                actual: (8, 4),
+                expected: (4, 4)
+            })
+        ));
+    }
+}
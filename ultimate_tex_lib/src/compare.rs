@@ -0,0 +1,280 @@
+use std::fmt;
+
+use image_dds::{ImageFormat, SurfaceRgba32Float};
+
+use crate::{Error, ImageFile};
+
+/// The differences between two textures from [compare].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureComparison {
+    pub formats: (ImageFormat, ImageFormat),
+    /// The width, height, and depth of the base mipmap.
+    pub dimensions: ((u32, u32, u32), (u32, u32, u32)),
+    pub layer_counts: (u32, u32),
+    pub mipmap_counts: (u32, u32),
+    /// The name stored in the file for nutexb and bntx files.
+    pub internal_names: (Option<String>, Option<String>),
+    /// The pixel differences for each layer and mipmap in both textures.
+    /// This is empty if the dimensions do not match.
+    pub surfaces: Vec<SurfaceComparison>,
+}
+
+/// The pixel differences for a single layer and mipmap including all depth slices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurfaceComparison {
+    pub layer: u32,
+    pub mipmap: u32,
+    /// The number of pixels with at least one different channel.
+    pub different_pixels: usize,
+    /// The largest absolute difference for each RGBA channel.
+    pub max_error: [f32; 4],
+}
+
+impl TextureComparison {
+    /// Returns `true` if all properties and decoded pixels are the same.
+    pub fn is_identical(&self) -> bool {
+        self.formats.0 == self.formats.1
+            && self.dimensions.0 == self.dimensions.1
+            && self.layer_counts.0 == self.layer_counts.1
+            && self.mipmap_counts.0 == self.mipmap_counts.1
+            && self.internal_names.0 == self.internal_names.1
+            && self.surfaces.iter().all(|s| s.different_pixels == 0)
+    }
+}
+
+impl fmt::Display for TextureComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dimensions = |(w, h, d): (u32, u32, u32)| format!("{w}x{h}x{d}");
+        let name = |n: &Option<String>| n.clone().unwrap_or_else(|| "none".to_string());
+
+        write_property(f, "format", self.formats.0, self.formats.1)?;
+        write_property(
+            f,
+            "dimensions",
+            dimensions(self.dimensions.0),
+            dimensions(self.dimensions.1),
+        )?;
+        write_property(f, "layers", self.layer_counts.0, self.layer_counts.1)?;
+        write_property(f, "mipmaps", self.mipmap_counts.0, self.mipmap_counts.1)?;
+        write_property(
+            f,
+            "internal name",
+            name(&self.internal_names.0),
+            name(&self.internal_names.1),
+        )?;
+
+        if self.surfaces.is_empty() {
+            write!(f, "pixels: not compared due to different dimensions")?;
+        }
+        for (i, surface) in self.surfaces.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "layer {} mipmap {}: ", surface.layer, surface.mipmap)?;
+            if surface.different_pixels == 0 {
+                write!(f, "identical")?;
+            } else {
+                let [r, g, b, a] = surface.max_error;
+                write!(
+                    f,
+                    "{} different pixels, max error R {r:.4} G {g:.4} B {b:.4} A {a:.4}",
+                    surface.different_pixels
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_property<T: fmt::Display + PartialEq>(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    a: T,
+    b: T,
+) -> fmt::Result {
+    if a == b {
+        writeln!(f, "{name}: {a}")
+    } else {
+        writeln!(f, "{name}: {a} != {b}")
+    }
+}
+
+/// Compare the properties and decoded pixels for every layer and mipmap of `a` and `b`.
+///
+/// The files can use any container, so a nutexb can be compared with a PNG or DDS file.
+pub fn compare(a: &ImageFile, b: &ImageFile) -> Result<TextureComparison, Error> {
+    let surface_a = a.decode_rgbaf32()?;
+    let surface_b = b.decode_rgbaf32()?;
+
+    let dimensions = |s: &SurfaceRgba32Float<Vec<f32>>| (s.width, s.height, s.depth);
+
+    // Mipmap sizes only match if the base mipmap sizes match.
+    let surfaces = if dimensions(&surface_a) == dimensions(&surface_b) {
+        (0..surface_a.layers.min(surface_b.layers))
+            .flat_map(|layer| {
+                (0..surface_a.mipmaps.min(surface_b.mipmaps)).map(move |mipmap| (layer, mipmap))
+            })
+            .map(|(layer, mipmap)| compare_surface(&surface_a, &surface_b, layer, mipmap))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        Vec::new()
+    };
+
+    Ok(TextureComparison {
+        formats: (a.image_format()?, b.image_format()?),
+        dimensions: (dimensions(&surface_a), dimensions(&surface_b)),
+        layer_counts: (surface_a.layers, surface_b.layers),
+        mipmap_counts: (surface_a.mipmaps, surface_b.mipmaps),
+        internal_names: (a.internal_name(), b.internal_name()),
+        surfaces,
+    })
+}
+
+fn compare_surface(
+    a: &SurfaceRgba32Float<Vec<f32>>,
+    b: &SurfaceRgba32Float<Vec<f32>>,
+    layer: u32,
+    mipmap: u32,
+) -> Result<SurfaceComparison, Error> {
+    // Depth slices get smaller for each mipmap like width and height.
+    let depth = (a.depth >> mipmap).max(1);
+
+    let mut different_pixels = 0;
+    let mut max_error = [0.0f32; 4];
+    for slice in 0..depth {
+        let data_a = surface_data(a, layer, slice, mipmap)?;
+        let data_b = surface_data(b, layer, slice, mipmap)?;
+        for (pixel_a, pixel_b) in data_a.chunks_exact(4).zip(data_b.chunks_exact(4)) {
+            if pixel_a != pixel_b {
+                different_pixels += 1;
+            }
+            for (i, (a, b)) in pixel_a.iter().zip(pixel_b).enumerate() {
+                max_error[i] = max_error[i].max((a - b).abs());
+            }
+        }
+    }
+
+    Ok(SurfaceComparison {
+        layer,
+        mipmap,
+        different_pixels,
+        max_error,
+    })
+}
+
+fn surface_data(
+    surface: &SurfaceRgba32Float<Vec<f32>>,
+    layer: u32,
+    slice: u32,
+    mipmap: u32,
+) -> Result<&[f32], Error> {
+    surface.get(layer, slice, mipmap).ok_or_else(|| {
+        Error::decode(format!(
+            "Failed to get data for layer {layer} depth slice {slice} mipmap {mipmap}"
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image_dds::{Surface, SurfaceRgba8};
+
+    fn image(width: u32, height: u32, value: u8) -> ImageFile {
+        ImageFile::Image(SurfaceRgba8 {
+            width,
+            height,
+            depth: 1,
+            layers: 1,
+            mipmaps: 1,
+            data: vec![value; (width * height * 4) as usize],
+        })
+    }
+
+    fn dds_with_mipmaps(mipmaps: u32) -> ImageFile {
+        // 4x4, 2x2, and 1x1 mipmaps.
+        let size: usize = [64, 16, 4][..mipmaps as usize].iter().sum();
+        let surface = Surface {
+            width: 4,
+            height: 4,
+            depth: 1,
+            layers: 1,
+            mipmaps,
+            image_format: ImageFormat::Rgba8Unorm,
+            data: vec![255; size],
+        };
+        ImageFile::Dds(surface.to_dds().unwrap())
+    }
+
+    #[test]
+    fn compare_identical() {
+        let comparison = compare(&image(4, 4, 128), &image(4, 4, 128)).unwrap();
+        assert!(comparison.is_identical());
+        assert_eq!(
+            vec![SurfaceComparison {
+                layer: 0,
+                mipmap: 0,
+                different_pixels: 0,
+                max_error: [0.0; 4],
+            }],
+            comparison.surfaces
+        );
+    }
+
+    #[test]
+    fn compare_different_pixels() {
+        let a = image(2, 2, 0);
+        let mut b = image(2, 2, 0);
+        if let ImageFile::Image(surface) = &mut b {
+            surface.data[14] = 255;
+        }
+        let comparison = compare(&a, &b).unwrap();
+        assert!(!comparison.is_identical());
+        assert_eq!(1, comparison.surfaces[0].different_pixels);
+        assert_eq!([0.0, 0.0, 1.0, 0.0], comparison.surfaces[0].max_error);
+    }
+
+    #[test]
+    fn compare_different_formats() {
+        let a = image(4, 4, 255);
+        let b = ImageFile::ImageFloat(SurfaceRgba32Float {
+            width: 4,
+            height: 4,
+            depth: 1,
+            layers: 1,
+            mipmaps: 1,
+            data: vec![1.0; 64],
+        });
+        let comparison = compare(&a, &b).unwrap();
+        assert_eq!(
+            (ImageFormat::Rgba8Unorm, ImageFormat::Rgba32Float),
+            comparison.formats
+        );
+        assert_eq!(0, comparison.surfaces[0].different_pixels);
+        assert!(!comparison.is_identical());
+    }
+
+    #[test]
+    fn compare_different_dimensions() {
+        let comparison = compare(&image(4, 4, 0), &image(8, 4, 0)).unwrap();
+        assert_eq!(((4, 4, 1), (8, 4, 1)), comparison.dimensions);
+        assert!(comparison.surfaces.is_empty());
+        assert!(!comparison.is_identical());
+        assert!(
+            comparison
+                .to_string()
+                .ends_with("pixels: not compared due to different dimensions")
+        );
+    }
+
+    #[test]
+    fn compare_different_mipmaps() {
+        let comparison = compare(&dds_with_mipmaps(3), &dds_with_mipmaps(1)).unwrap();
+        assert_eq!((3, 1), comparison.mipmap_counts);
+        // Only mipmaps in both files are compared.
+        assert_eq!(1, comparison.surfaces.len());
+        assert_eq!(0, comparison.surfaces[0].different_pixels);
+        assert!(!comparison.is_identical());
+    }
+}
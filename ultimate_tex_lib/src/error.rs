@@ -0,0 +1,147 @@
+use std::path::PathBuf;
+
+use crate::Container;
+
+type BoxedError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Errors while reading, converting, or writing texture files.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The image format is not supported by the container or by image_dds.
+    #[error("unsupported {container} image format: {reason}")]
+    UnsupportedFormat {
+        container: Container,
+        reason: String,
+    },
+
+    /// The file data is not valid for the container.
+    #[error("invalid {container} file: {reason}")]
+    InvalidContainer {
+        container: Container,
+        reason: String,
+    },
+
+    // Errors with the underlying error in the message don't set it as the source.
+    // This avoids printing the same message twice for error chains.
+    /// The image data could not be decoded.
+    #[error("failed to decode image data: {0}")]
+    Decode(BoxedError),
+
+    /// The image data could not be encoded to the requested format.
+    #[error("failed to encode image data: {0}")]
+    Encode(BoxedError),
+
+    /// The images for each layer do not have the same dimensions.
+    #[error("layer {layer} has dimensions {actual:?} but expected {expected:?}")]
+    LayerDimensions {
+        layer: usize,
+        actual: (u32, u32),
+        expected: (u32, u32),
+    },
+
+    #[error("expected at least one layer image")]
+    MissingLayers,
+
+    /// The images for each packed channel do not have the same dimensions.
+    #[error("channel {channel} has dimensions {actual:?} but expected {expected:?}")]
+    ChannelDimensions {
+        channel: usize,
+        actual: (u32, u32),
+        expected: (u32, u32),
+    },
+
+    /// The atlas image can't be evenly split into the requested depth slices.
+    #[error(
+        "atlas with dimensions {width}x{height} cannot be split into {depth} slices with {columns} columns"
+    )]
+    AtlasDimensions {
+        width: u32,
+        height: u32,
+        depth: u32,
+        columns: u32,
+    },
+
+    /// The images being compared do not have the same width, height, depth, and layer count.
+    #[error("image has dimensions {actual:?} but expected {expected:?}")]
+    CompareDimensions {
+        actual: (u32, u32, u32, u32),
+        expected: (u32, u32, u32, u32),
+    },
+
+    /// A BNTX file does not contain a texture with the given name.
+    #[error("no texture named {name:?}")]
+    MissingTexture { name: String },
+
+    /// The replacement for a texture has a different width, height, or depth than the texture.
+    #[error(
+        "texture {name:?} has dimensions {expected:?} but the replacement has dimensions {actual:?}"
+    )]
+    TextureDimensions {
+        name: String,
+        actual: (u32, u32, u32),
+        expected: (u32, u32, u32),
+    },
+
+    /// The replacement for a texture has a different number of array layers than the texture.
+    #[error("texture {name:?} has {expected} layers but the replacement has {actual} layers")]
+    TextureLayers {
+        name: String,
+        actual: u32,
+        expected: u32,
+    },
+
+    /// Removing padding from a nutexb file would change the decoded image data.
+    #[error("optimized image data does not match the original image data")]
+    OptimizedDataMismatch,
+
+    /// Multiple files in a batch would be saved to the same output path.
+    #[error("output {output:?} is also the output for {other:?}")]
+    DuplicateOutput { output: PathBuf, other: PathBuf },
+
+    /// The output path for a file in a batch is also an input path.
+    #[error("output {output:?} would overwrite an input file")]
+    OutputIsInput { output: PathBuf },
+
+    /// The conversion was cancelled before saving the file.
+    #[error("conversion was cancelled")]
+    Cancelled,
+
+    /// The presets file is not valid JSON or has invalid settings.
+    #[error("invalid presets: {0}")]
+    Presets(serde_json::Error),
+
+    #[error(transparent)]
+    Image(#[from] image_dds::image::ImageError),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Presets(e)
+    }
+}
+
+impl Error {
+    pub(crate) fn decode<E: Into<BoxedError>>(e: E) -> Self {
+        Self::Decode(e.into())
+    }
+
+    pub(crate) fn encode<E: Into<BoxedError>>(e: E) -> Self {
+        Self::Encode(e.into())
+    }
+
+    pub(crate) fn write<E: Into<BoxedError>>(e: E) -> Self {
+        Self::Io(std::io::Error::other(e))
+    }
+
+    pub(crate) fn invalid_container<E: std::fmt::Display>(
+        container: Container,
+    ) -> impl FnOnce(E) -> Self {
+        move |e| Self::InvalidContainer {
+            container,
+            reason: e.to_string(),
+        }
+    }
+}
@@ -0,0 +1,475 @@
+use std::{
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use image_dds::{
+    ImageFormat, dds_image_format,
+    ddsfile::{Caps2, Dds, FourCC, Header, Header10, MiscFlag},
+    image::{ImageDecoder, ImageReader},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ColorSpace, Container, Error, FileReader, ImageFile, NUTEXB_FOOTER_SIZE, NutexbFile, Slices,
+    bntx_archive::read_first_texture, bntx_to_dds, color_space, file_name_no_extension,
+    image_reader, is_high_precision_color, nutexb_image_format, open_file,
+};
+
+/// Texture properties read from the file header without decoding image data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextureInfo {
+    pub container: Container,
+    pub format: ImageFormat,
+    /// The color space of [TextureInfo::format].
+    /// Image files like PNG are always linear since they are loaded as Rgba8Unorm or Rgba32Float.
+    pub color_space: ColorSpace,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    /// The number of array layers or cube map faces.
+    pub layer_count: u32,
+    pub mipmap_count: u32,
+    /// `true` if the texture has multiple array layers
+    /// or multiple cube maps for cube map arrays.
+    pub is_array: bool,
+    pub is_cube: bool,
+    /// The name stored in the file for nutexb and bntx files.
+    pub internal_name: Option<String>,
+    /// The bits per channel of the source data for image files like 16-bit PNG.
+    /// This is `None` for other containers or if the source data is unknown.
+    ///
+    /// Images with more than 8 bits per channel use Rgba32Float for [TextureInfo::format]
+    /// to avoid losing precision when loading.
+    pub bits_per_channel: Option<u32>,
+    /// The size in bytes of the image data for all layers and mipmaps.
+    /// This is the size of the entire file for image formats like PNG.
+    pub data_size: u64,
+}
+
+impl TextureInfo {
+    pub fn from_file<P: AsRef<Path>>(input: P) -> Result<Self, Error> {
+        match open_file(input.as_ref())? {
+            FileReader::Container(mut reader, container) => {
+                Self::from_reader(&mut reader, container)
+            }
+            FileReader::Image(image) => {
+                let data_size = std::fs::metadata(input.as_ref())?.len();
+                image_info(image, data_size)
+            }
+        }
+    }
+
+    /// Read the header from `reader` for the given file type.
+    pub fn from_reader<R: Read + Seek>(
+        reader: &mut R,
+        container: Container,
+    ) -> Result<Self, Error> {
+        match container {
+            Container::Nutexb => nutexb_info(reader),
+            Container::Bntx => {
+                // The texture headers use offsets from the start of the file.
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data)?;
+                bntx_info(&data)
+            }
+            Container::Dds => dds_info(reader),
+            Container::Image => {
+                let start = reader.stream_position()?;
+                let data_size = reader.seek(SeekFrom::End(0))? - start;
+                reader.seek(SeekFrom::Start(start))?;
+
+                image_info(image_reader(BufReader::new(reader), None)?, data_size)
+            }
+        }
+    }
+
+    /// Get the info for a texture combined from separate images with [ImageFile::from_slice_files]
+    /// without decoding the image data.
+    pub fn from_slice_files<P: AsRef<Path>>(inputs: &[P], slices: Slices) -> Result<Self, Error> {
+        let infos = inputs
+            .iter()
+            .map(Self::from_file)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let first = infos.first().ok_or(Error::MissingLayers)?;
+        let expected = (first.width, first.height);
+        if let Some(i) = infos.iter().position(|i| (i.width, i.height) != expected) {
+            return Err(Error::LayerDimensions {
+                layer: i,
+                actual: (infos[i].width, infos[i].height),
+                expected,
+            });
+        }
+
+        // Match the format used when loading the images.
+        let source = infos.iter().max_by_key(|i| i.bits_per_channel).unwrap();
+        let (layer_count, depth) = slices.layers_and_depth(infos.len() as u32);
+        let is_cube = slices == Slices::Layers && layer_count == 6;
+        Ok(Self {
+            container: Container::Image,
+            format: source.format,
+            color_space: source.color_space,
+            width: first.width,
+            height: first.height,
+            depth,
+            layer_count,
+            mipmap_count: 1,
+            is_array: is_array(layer_count, is_cube),
+            is_cube,
+            internal_name: None,
+            bits_per_channel: source.bits_per_channel,
+            data_size: infos.iter().map(|i| i.data_size).sum(),
+        })
+    }
+
+    /// Get the info for an already loaded file.
+    pub fn from_image(image: &ImageFile) -> Result<Self, Error> {
+        let (width, height, depth) = image.dimensions();
+        let layer_count = image.layer_count();
+        let (mipmap_count, is_cube, data_size) = match image {
+            ImageFile::Image(surface) => (surface.mipmaps, false, surface.data.len() as u64),
+            ImageFile::ImageFloat(surface) => (
+                surface.mipmaps,
+                false,
+                (surface.data.len() * size_of::<f32>()) as u64,
+            ),
+            ImageFile::Dds(dds) => (
+                dds.get_num_mipmap_levels(),
+                is_dds_cube(dds),
+                dds.data.len() as u64,
+            ),
+            ImageFile::Nutexb(nutexb) => (
+                nutexb.footer.mipmap_count,
+                nutexb.footer.layer_count == 6,
+                nutexb.footer.data_size as u64,
+            ),
+            ImageFile::Bntx { bntx, .. } => {
+                let dds = bntx_to_dds(bntx)?;
+                (
+                    dds.get_num_mipmap_levels(),
+                    is_dds_cube(&dds),
+                    dds.data.len() as u64,
+                )
+            }
+        };
+
+        let format = image.image_format()?;
+        Ok(Self {
+            container: image.container(),
+            format,
+            color_space: color_space(format),
+            width,
+            height,
+            depth,
+            layer_count,
+            mipmap_count,
+            is_array: is_array(layer_count, is_cube),
+            is_cube,
+            internal_name: image.internal_name(),
+            // Images with more than 8 bits per channel are converted to floating point when loading.
+            bits_per_channel: matches!(image, ImageFile::Image(_)).then_some(8),
+            data_size,
+        })
+    }
+
+    /// The format for converting the texture without changing the format where possible.
+    ///
+    /// This is [TextureInfo::format] except for 16-bit images like height maps.
+    /// These use Rgba8Unorm instead of the much larger Rgba32Float used for loading.
+    pub fn default_output_format(&self) -> ImageFormat {
+        match self.bits_per_channel {
+            Some(16) => ImageFormat::Rgba8Unorm,
+            _ => self.format,
+        }
+    }
+
+    /// The name stored in the file or the file name of `output` without the extension.
+    pub fn default_internal_name(&self, output: &Path) -> String {
+        self.internal_name
+            .clone()
+            .unwrap_or_else(|| file_name_no_extension(output))
+    }
+}
+
+fn nutexb_info<R: Read + Seek>(reader: &mut R) -> Result<TextureInfo, Error> {
+    // Nutexb files store image data first, so only read the footer at the end.
+    let start = reader.stream_position()?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    if end - start < NUTEXB_FOOTER_SIZE {
+        return Err(Error::InvalidContainer {
+            container: Container::Nutexb,
+            reason: "file is smaller than the footer".to_string(),
+        });
+    }
+    reader.seek(SeekFrom::Start(end - NUTEXB_FOOTER_SIZE))?;
+    let mut footer = [0u8; NUTEXB_FOOTER_SIZE as usize];
+    reader.read_exact(&mut footer)?;
+
+    let u32_at = |offset: usize| u32::from_le_bytes(footer[offset..offset + 4].try_into().unwrap());
+
+    let Some(format) = nutexb_format(u32_at(0x50)) else {
+        // Let the nutexb crate handle any unrecognized formats.
+        reader.seek(SeekFrom::Start(start))?;
+        let nutexb =
+            NutexbFile::read(reader).map_err(Error::invalid_container(Container::Nutexb))?;
+        return TextureInfo::from_image(&ImageFile::Nutexb(nutexb));
+    };
+
+    let name = &footer[0x04..0x44];
+    let name = name.split(|b| *b == 0).next().unwrap_or_default();
+
+    let layer_count = u32_at(0x60);
+    let is_cube = layer_count == 6;
+    Ok(TextureInfo {
+        container: Container::Nutexb,
+        format,
+        color_space: color_space(format),
+        width: u32_at(0x44),
+        height: u32_at(0x48),
+        depth: u32_at(0x4C),
+        layer_count,
+        mipmap_count: u32_at(0x58),
+        is_array: is_array(layer_count, is_cube),
+        is_cube,
+        internal_name: Some(String::from_utf8_lossy(name).to_string()),
+        bits_per_channel: None,
+        data_size: u64::from(u32_at(0x64)),
+    })
+}
+
+// Match the format values from the nutexb crate without parsing the full file.
+fn nutexb_format(value: u32) -> Option<ImageFormat> {
+    use nutexb::NutexbFormat::*;
+    let format = [
+        R8Unorm,
+        R8G8B8A8Unorm,
+        R8G8B8A8Srgb,
+        R32G32B32A32Float,
+        B8G8R8A8Unorm,
+        B8G8R8A8Srgb,
+        BC1Unorm,
+        BC1Srgb,
+        BC2Unorm,
+        BC2Srgb,
+        BC3Unorm,
+        BC3Srgb,
+        BC4Unorm,
+        BC4Snorm,
+        BC5Unorm,
+        BC5Snorm,
+        BC6Ufloat,
+        BC6Sfloat,
+        BC7Unorm,
+        BC7Srgb,
+    ]
+    .into_iter()
+    .find(|f| *f as u32 == value)?;
+    Some(nutexb_image_format(format))
+}
+
+fn bntx_info(data: &[u8]) -> Result<TextureInfo, Error> {
+    let texture = read_first_texture(data)?;
+
+    let format = texture.image_format()?;
+    let is_cube = texture.layer_count == 6;
+    Ok(TextureInfo {
+        container: Container::Bntx,
+        format,
+        color_space: color_space(format),
+        width: texture.width,
+        height: texture.height,
+        depth: texture.depth,
+        layer_count: texture.layer_count,
+        mipmap_count: texture.mipmap_count,
+        is_array: is_array(texture.layer_count, is_cube),
+        is_cube,
+        internal_name: Some(texture.name),
+        bits_per_channel: None,
+        data_size: texture.data_size as u64,
+    })
+}
+
+fn dds_info<R: Read + Seek>(reader: &mut R) -> Result<TextureInfo, Error> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != *b"DDS " {
+        return Err(Error::InvalidContainer {
+            container: Container::Dds,
+            reason: "missing DDS magic".to_string(),
+        });
+    }
+
+    let header = Header::read(&mut *reader).map_err(Error::invalid_container(Container::Dds))?;
+    let header10 = if header.spf.fourcc == Some(FourCC(FourCC::DX10)) {
+        Some(Header10::read(&mut *reader).map_err(Error::invalid_container(Container::Dds))?)
+    } else {
+        None
+    };
+
+    // The image data is everything after the header.
+    let start = reader.stream_position()?;
+    let data_size = reader.seek(SeekFrom::End(0))? - start;
+
+    // The header is enough to determine the format and dimensions.
+    let dds = Dds {
+        header,
+        header10,
+        data: Vec::new(),
+    };
+    let format = dds_image_format(&dds).map_err(|e| Error::UnsupportedFormat {
+        container: Container::Dds,
+        reason: e.to_string(),
+    })?;
+
+    let is_cube = is_dds_cube(&dds);
+    let layer_count = dds.get_num_array_layers() * if is_cube { 6 } else { 1 };
+    Ok(TextureInfo {
+        container: Container::Dds,
+        format,
+        color_space: color_space(format),
+        width: dds.get_width(),
+        height: dds.get_height(),
+        depth: dds.get_depth(),
+        layer_count,
+        mipmap_count: dds.get_num_mipmap_levels(),
+        is_array: is_array(layer_count, is_cube),
+        is_cube,
+        internal_name: None,
+        bits_per_channel: None,
+        data_size,
+    })
+}
+
+fn image_info<R: BufRead + Seek>(
+    image: ImageReader<R>,
+    data_size: u64,
+) -> Result<TextureInfo, Error> {
+    let decoder = image.into_decoder()?;
+    let (width, height) = decoder.dimensions();
+
+    // Match the format used when loading the image.
+    let color = decoder.color_type();
+    let format = if is_high_precision_color(color) {
+        ImageFormat::Rgba32Float
+    } else {
+        ImageFormat::Rgba8Unorm
+    };
+
+    Ok(TextureInfo {
+        container: Container::Image,
+        format,
+        color_space: color_space(format),
+        width,
+        height,
+        depth: 1,
+        layer_count: 1,
+        mipmap_count: 1,
+        is_array: false,
+        is_cube: false,
+        internal_name: None,
+        bits_per_channel: Some((color.bits_per_pixel() / color.channel_count() as u16) as u32),
+        data_size,
+    })
+}
+
+fn is_dds_cube(dds: &Dds) -> bool {
+    dds.header.caps2.contains(Caps2::CUBEMAP)
+        || dds
+            .header10
+            .as_ref()
+            .is_some_and(|h| h.misc_flag.contains(MiscFlag::TEXTURECUBE))
+}
+
+fn is_array(layer_count: u32, is_cube: bool) -> bool {
+    let faces = if is_cube { 6 } else { 1 };
+    layer_count > faces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use image_dds::Surface;
+
+    use crate::Bntx;
+
+    fn surface(layers: u32, mipmaps: u32) -> Surface<Vec<u8>> {
+        // 4x4, 2x2, and 1x1 mipmaps for each layer.
+        let layer_size: usize = [64, 16, 4][..mipmaps as usize].iter().sum();
+        Surface {
+            width: 4,
+            height: 4,
+            depth: 1,
+            layers,
+            mipmaps,
+            image_format: ImageFormat::Rgba8UnormSrgb,
+            data: vec![0; layer_size * layers as usize],
+        }
+    }
+
+    fn dds_bytes(layers: u32, mipmaps: u32) -> Vec<u8> {
+        let dds = surface(layers, mipmaps).to_dds().unwrap();
+        let mut writer = Cursor::new(Vec::new());
+        dds.write(&mut writer).unwrap();
+        writer.into_inner()
+    }
+
+    #[test]
+    fn nutexb_info_matches_footer() {
+        let dds = surface(1, 3).to_dds().unwrap();
+        let nutexb = NutexbFile::from_dds(&dds, "def_mario_001_col".to_string()).unwrap();
+        let mut writer = Cursor::new(Vec::new());
+        nutexb.write(&mut writer).unwrap();
+        let bytes = writer.into_inner();
+
+        let info = nutexb_info(&mut Cursor::new(&bytes)).unwrap();
+        let footer = NutexbFile::read(&mut Cursor::new(&bytes)).unwrap().footer;
+        assert_eq!(nutexb_image_format(footer.image_format), info.format);
+        assert_eq!(
+            (footer.width, footer.height, footer.depth),
+            (info.width, info.height, info.depth)
+        );
+        assert_eq!(footer.layer_count, info.layer_count);
+        assert_eq!(footer.mipmap_count, info.mipmap_count);
+        assert_eq!(Some(footer.string.to_string()), info.internal_name);
+        assert_eq!(footer.data_size as u64, info.data_size);
+    }
+
+    #[test]
+    fn dds_info_matches_dds() {
+        for (layers, mipmaps) in [(1, 1), (1, 3), (2, 2), (6, 3)] {
+            let bytes = dds_bytes(layers, mipmaps);
+            let info = dds_info(&mut Cursor::new(&bytes)).unwrap();
+            let dds = Dds::read(&mut Cursor::new(&bytes)).unwrap();
+            assert_eq!(
+                TextureInfo::from_image(&ImageFile::Dds(dds)).unwrap(),
+                info,
+                "{layers} layers and {mipmaps} mipmaps"
+            );
+        }
+    }
+
+    #[test]
+    fn bntx_info_matches_bntx() {
+        let dds = surface(1, 3).to_dds().unwrap();
+        let bntx = Bntx::from_dds(&dds, "chara_0_mario_01").unwrap();
+        let mut writer = Cursor::new(Vec::new());
+        bntx.write(&mut writer).unwrap();
+        let bytes = writer.into_inner();
+
+        let info = TextureInfo::from_reader(&mut Cursor::new(&bytes), Container::Bntx).unwrap();
+        let image = ImageFile::from_bytes(&bytes, Container::Bntx).unwrap();
+        let expected = TextureInfo::from_image(&image).unwrap();
+        // The header uses the size of the swizzled data with padding.
+        assert_eq!(
+            TextureInfo {
+                data_size: expected.data_size,
+                ..info
+            },
+            expected
+        );
+    }
+}
@@ -1,172 +1,608 @@
-use std::path::Path;
+use std::{
+    io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
 
 pub use bntx::Bntx;
 pub use nutexb::NutexbFile;
 
-use image_dds::{ImageFormat, Surface, dds_image_format, ddsfile::Dds, image::RgbaImage};
+use image_dds::{
+    ImageFormat, Mipmaps, Surface, SurfaceRgba8, SurfaceRgba32Float, dds_image_format,
+    ddsfile::Dds,
+    image::{
+        ColorType, DynamicImage, ImageBuffer, ImageReader, Pixel, Rgba32FImage, RgbaImage, imageops,
+    },
+};
+
+mod batch;
+mod bntx_archive;
+mod channels;
+mod compare;
+mod error;
+mod info;
+mod metrics;
+mod optimize;
+mod presets;
+mod validate;
+
+pub use batch::{
+    BatchFile, BatchJob, BatchOutput, BatchProgress, BatchResult, ConvertedFile, INPUT_EXTENSIONS,
+    ImageExport, InputFile, OutputOverrides, SaveOptions, find_input_files,
+};
+pub use bntx_archive::{BntxArchive, BntxTexture};
+pub use channels::{Channel, ChannelSource, PackChannel, pack_channels};
+pub use compare::{SurfaceComparison, TextureComparison, compare};
+pub use error::Error;
+pub use info::TextureInfo;
+pub use metrics::QualityMetrics;
+pub use optimize::{
+    OptimizeReport, OptimizedFile, find_nutexb_files, optimize_nutexb_file, optimize_nutexb_files,
+};
+pub use presets::{ColorSpace, OutputSettings, PresetRule, Presets, color_space, with_color_space};
+pub use validate::{MAX_DIMENSION, TextureProperties, ValidationIssue, validate, validate_file};
+
+use bntx_archive::read_first_texture;
+
+/// The file name suffixes for cube map faces in layer order.
+pub const CUBE_FACE_SUFFIXES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+// The nutexb footer is stored at the end of the file after the image data.
+const NUTEXB_FOOTER_SIZE: u64 = 0x70;
+
+/// The file type for texture data.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, strum::Display, serde::Serialize, serde::Deserialize,
+)]
+pub enum Container {
+    Nutexb,
+    Bntx,
+    Dds,
+    /// Image formats supported by the image crate like PNG or TIFF.
+    Image,
+}
+
+impl Container {
+    /// The container for the file extension of `path`.
+    /// Unrecognized extensions are assumed to be image formats.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match extension(path.as_ref()).as_str() {
+            "nutexb" => Self::Nutexb,
+            "bntx" => Self::Bntx,
+            "dds" => Self::Dds,
+            _ => Self::Image,
+        }
+    }
+
+    /// Detect the container from magic numbers in the data of `reader`.
+    /// Returns `None` if the data doesn't match any known container.
+    ///
+    /// The reader is returned to its original position afterwards.
+    pub fn from_contents<R: Read + Seek>(reader: &mut R) -> std::io::Result<Option<Self>> {
+        let start = reader.stream_position()?;
+        let container = detect_container(reader, start);
+        reader.seek(SeekFrom::Start(start))?;
+        container
+    }
+}
+
+/// The output file type for converted textures.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    Clone,
+    Copy,
+    Default,
+    strum::Display,
+    strum::EnumIter,
+    strum::EnumString,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub enum ImageFileType {
+    Dds,
+    #[default]
+    Png,
+    Tiff,
+    Exr,
+    Nutexb,
+    Bntx,
+}
+
+impl ImageFileType {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFileType::Dds => "dds",
+            ImageFileType::Png => "png",
+            ImageFileType::Tiff => "tiff",
+            ImageFileType::Exr => "exr",
+            ImageFileType::Nutexb => "nutexb",
+            ImageFileType::Bntx => "bntx",
+        }
+    }
+
+    /// The container for files of this type.
+    pub fn container(&self) -> Container {
+        match self {
+            ImageFileType::Dds => Container::Dds,
+            ImageFileType::Png | ImageFileType::Tiff | ImageFileType::Exr => Container::Image,
+            ImageFileType::Nutexb => Container::Nutexb,
+            ImageFileType::Bntx => Container::Bntx,
+        }
+    }
+
+    /// Returns `true` if the file type stores an internal name like nutexb or bntx.
+    pub fn has_internal_name(&self) -> bool {
+        matches!(self, ImageFileType::Nutexb | ImageFileType::Bntx)
+    }
+}
 
 pub enum ImageFile {
-    Image(RgbaImage),
+    /// An uncompressed image with one or more array layers or cube map faces.
+    Image(SurfaceRgba8<Vec<u8>>),
+    /// An uncompressed floating point image for HDR data
+    /// or images with more than 8 bits per channel.
+    ImageFloat(SurfaceRgba32Float<Vec<f32>>),
     Dds(Dds),
     Nutexb(NutexbFile),
-    Bntx(Bntx),
+    /// A BNTX file with a single texture.
+    ///
+    /// Only the first texture is converted, and saved files contain a single texture.
+    /// Use [BntxArchive] to export or replace the textures in files with multiple textures.
+    Bntx {
+        bntx: Bntx,
+        /// The texture name read when loading since [Bntx] doesn't expose the name.
+        name: String,
+    },
 }
 
 impl ImageFile {
-    pub fn from_file<P: AsRef<Path>>(input: P) -> anyhow::Result<Self> {
-        match input
-            .as_ref()
-            .extension()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_lowercase()
-            .as_str()
-        {
-            "nutexb" => {
-                let mut nutexb = NutexbFile::read_from_file(input)?;
+    pub fn from_file<P: AsRef<Path>>(input: P) -> Result<Self, Error> {
+        match open_file(input.as_ref())? {
+            FileReader::Container(mut reader, container) => {
+                Self::from_reader(&mut reader, container)
+            }
+            FileReader::Image(image) => Ok(Self::from_dynamic_image(image.decode()?)),
+        }
+    }
+
+    /// Read from `bytes` for the given file type.
+    pub fn from_bytes(bytes: &[u8], container: Container) -> Result<Self, Error> {
+        Self::from_reader(&mut Cursor::new(bytes), container)
+    }
+
+    /// Read from `reader` for the given file type.
+    pub fn from_reader<R: Read + Seek>(
+        reader: &mut R,
+        container: Container,
+    ) -> Result<Self, Error> {
+        match container {
+            Container::Nutexb => {
+                let mut nutexb = NutexbFile::read(reader)
+                    .map_err(Error::invalid_container(Container::Nutexb))?;
                 // Some nutexbs saved with older tools have too many mipmaps specified.
                 // The image data is usually still valid.
                 // Attempt to fix the mipmap count here to keep this as an error in image_dds.
                 fix_mipmap_count(&mut nutexb);
                 Ok(ImageFile::Nutexb(nutexb))
             }
-            "bntx" => Ok(ImageFile::Bntx(Bntx::from_file(input)?)),
-            "dds" => {
-                let mut reader = std::io::BufReader::new(std::fs::File::open(input)?);
-                Ok(ImageFile::Dds(Dds::read(&mut reader)?))
+            Container::Bntx => {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data)?;
+                let bntx = Bntx::read(&mut Cursor::new(&data))
+                    .map_err(Error::invalid_container(Container::Bntx))?;
+                let name = read_first_texture(&data)?.name;
+                Ok(ImageFile::Bntx { bntx, name })
+            }
+            Container::Dds => {
+                let dds = Dds::read(reader).map_err(Error::invalid_container(Container::Dds))?;
+                Ok(ImageFile::Dds(dds))
             }
-            // Assume the other formats are image formats.
-            _ => Ok(ImageFile::Image(image_dds::image::open(input)?.to_rgba8())),
+            Container::Image => Self::from_image_reader(reader, None),
+        }
+    }
+
+    /// Read an image format like PNG or TGA from `reader`.
+    ///
+    /// The format is detected from the contents if possible.
+    /// Use `format` for formats like TGA without magic bytes,
+    /// such as from [image_dds::image::ImageFormat::from_extension].
+    pub fn from_image_reader<R: Read + Seek>(
+        reader: &mut R,
+        format: Option<image_dds::image::ImageFormat>,
+    ) -> Result<Self, Error> {
+        let image = image_reader(BufReader::new(reader), format)?.decode()?;
+        Ok(Self::from_dynamic_image(image))
+    }
+
+    fn from_dynamic_image(image: DynamicImage) -> Self {
+        if is_high_precision(&image) {
+            // Floating point preserves 16-bit and HDR values like EXR.
+            ImageFile::ImageFloat(rgbaf32_surface(image.to_rgba32f()))
+        } else {
+            ImageFile::Image(rgba8_surface(image.to_rgba8()))
+        }
+    }
+
+    /// Load a cube map, array texture, or 3D texture from separate images for each slice.
+    /// Cube maps should use 6 images in the order of [CUBE_FACE_SUFFIXES].
+    pub fn from_slice_files<P: AsRef<Path>>(inputs: &[P], slices: Slices) -> Result<Self, Error> {
+        let images = inputs
+            .iter()
+            .map(|input| Ok(image_dds::image::open(input)?))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        // Avoid losing precision if any of the images use more than 8 bits per channel.
+        if images.iter().any(is_high_precision) {
+            let images = images.into_iter().map(|i| i.to_rgba32f()).collect();
+            Self::from_slice_images_f32(images, slices)
+        } else {
+            let images = images.into_iter().map(|i| i.to_rgba8()).collect();
+            Self::from_slice_images(images, slices)
+        }
+    }
+
+    /// Combine images for each slice into a single cube map, array texture, or 3D texture.
+    /// Cube maps should use 6 images in the order of [CUBE_FACE_SUFFIXES].
+    pub fn from_slice_images(images: Vec<RgbaImage>, slices: Slices) -> Result<Self, Error> {
+        let (width, height, count, data) = combine_layers(images)?;
+        let (layers, depth) = slices.layers_and_depth(count);
+        Ok(ImageFile::Image(SurfaceRgba8 {
+            width,
+            height,
+            depth,
+            layers,
+            mipmaps: 1,
+            data,
+        }))
+    }
+
+    /// Combine floating point images for each slice into a single cube map, array texture, or 3D texture.
+    /// Cube maps should use 6 images in the order of [CUBE_FACE_SUFFIXES].
+    pub fn from_slice_images_f32(images: Vec<Rgba32FImage>, slices: Slices) -> Result<Self, Error> {
+        let (width, height, count, data) = combine_layers(images)?;
+        let (layers, depth) = slices.layers_and_depth(count);
+        Ok(ImageFile::ImageFloat(SurfaceRgba32Float {
+            width,
+            height,
+            depth,
+            layers,
+            mipmaps: 1,
+            data,
+        }))
+    }
+
+    /// Create a 3D texture with `depth` slices from an image with slices
+    /// tiled left to right and top to bottom with `columns` slices per row.
+    pub fn from_depth_atlas(atlas: DynamicImage, depth: u32, columns: u32) -> Result<Self, Error> {
+        if is_high_precision(&atlas) {
+            let images = split_atlas(&atlas.to_rgba32f(), depth, columns)?;
+            Self::from_slice_images_f32(images, Slices::Depth)
+        } else {
+            let images = split_atlas(&atlas.to_rgba8(), depth, columns)?;
+            Self::from_slice_images(images, Slices::Depth)
         }
     }
 
-    pub fn image_format(&self) -> ImageFormat {
-        // TODO: Avoid unwrap?
+    pub fn image_format(&self) -> Result<ImageFormat, Error> {
         match self {
-            ImageFile::Image(_) => ImageFormat::Rgba8Unorm,
-            ImageFile::Dds(dds) => image_dds::dds_image_format(dds).unwrap(),
-            ImageFile::Nutexb(nutexb) => nutexb_image_format(nutexb),
-            ImageFile::Bntx(bntx) => bntx_image_format(bntx),
+            ImageFile::Image(_) => Ok(ImageFormat::Rgba8Unorm),
+            ImageFile::ImageFloat(_) => Ok(ImageFormat::Rgba32Float),
+            ImageFile::Dds(dds) => dds_image_format(dds).map_err(|e| Error::UnsupportedFormat {
+                container: Container::Dds,
+                reason: e.to_string(),
+            }),
+            ImageFile::Nutexb(nutexb) => Ok(nutexb_image_format(nutexb.footer.image_format)),
+            ImageFile::Bntx { bntx, .. } => bntx_image_format(bntx),
         }
     }
 
     pub fn dimensions(&self) -> (u32, u32, u32) {
         match self {
-            ImageFile::Image(image) => (image.width(), image.height(), 1),
+            ImageFile::Image(surface) => (surface.width, surface.height, surface.depth),
+            ImageFile::ImageFloat(surface) => (surface.width, surface.height, surface.depth),
             ImageFile::Dds(dds) => (dds.get_width(), dds.get_height(), dds.get_depth()),
             ImageFile::Nutexb(nutexb) => (
                 nutexb.footer.width,
                 nutexb.footer.height,
                 nutexb.footer.depth,
             ),
-            ImageFile::Bntx(bntx) => (bntx.width(), bntx.height(), bntx.depth()),
+            ImageFile::Bntx { bntx, .. } => (bntx.width(), bntx.height(), bntx.depth()),
         }
     }
 
-    pub fn to_image(&self) -> anyhow::Result<RgbaImage> {
-        // TODO: EXR support for BC6H?
+    /// The number of array layers or cube map faces.
+    pub fn layer_count(&self) -> u32 {
         match self {
-            ImageFile::Image(image) => Ok(image.clone()),
-            ImageFile::Dds(dds) => image_dds::image_from_dds(dds, 0).map_err(Into::into),
+            ImageFile::Image(surface) => surface.layers,
+            ImageFile::ImageFloat(surface) => surface.layers,
+            ImageFile::Dds(dds) => dds_layer_count(dds),
+            ImageFile::Nutexb(nutexb) => nutexb.footer.layer_count,
+            ImageFile::Bntx { bntx, .. } => bntx_to_dds(bntx)
+                .map(|dds| dds_layer_count(&dds))
+                .unwrap_or(1),
+        }
+    }
+
+    pub fn to_image(&self) -> Result<RgbaImage, Error> {
+        match self {
+            ImageFile::Image(surface) => surface
+                .get(0, 0, 0)
+                .and_then(|data| RgbaImage::from_raw(surface.width, surface.height, data.to_vec()))
+                .ok_or_else(|| Error::decode("Failed to create image from surface")),
+            ImageFile::ImageFloat(_) => {
+                Ok(DynamicImage::ImageRgba32F(self.to_image_f32()?).to_rgba8())
+            }
+            ImageFile::Dds(dds) => image_dds::image_from_dds(dds, 0).map_err(Error::decode),
             ImageFile::Nutexb(nutexb) => {
                 // Use DDS as an intermediate format to handle swizzling.
-                let dds = nutexb.to_dds()?;
-                image_dds::image_from_dds(&dds, 0).map_err(Into::into)
+                let dds = nutexb_to_dds(nutexb)?;
+                image_dds::image_from_dds(&dds, 0).map_err(Error::decode)
             }
-            ImageFile::Bntx(bntx) => {
+            ImageFile::Bntx { bntx, .. } => {
                 // Use DDS as an intermediate format to handle swizzling.
-                let dds = bntx.to_dds()?;
-                image_dds::image_from_dds(&dds, 0).map_err(Into::into)
+                let dds = bntx_to_dds(bntx)?;
+                image_dds::image_from_dds(&dds, 0).map_err(Error::decode)
+            }
+        }
+    }
+
+    /// Decode the base mipmap to floating point without clamping HDR values for formats like BC6H.
+    pub fn to_image_f32(&self) -> Result<Rgba32FImage, Error> {
+        let surface = self.decode_rgbaf32()?;
+        surface
+            .get(0, 0, 0)
+            .and_then(|data| Rgba32FImage::from_raw(surface.width, surface.height, data.to_vec()))
+            .ok_or_else(|| Error::decode("Failed to create image from surface"))
+    }
+
+    /// Decode the base mipmap of each array layer, cube map face, or depth slice.
+    /// Depth slices use the first layer.
+    pub fn to_slice_images(&self, slices: Slices) -> Result<Vec<RgbaImage>, Error> {
+        let surface = self.decode_rgba8()?;
+        let count = slices.count(surface.layers, surface.depth);
+        slice_images(
+            surface.width,
+            surface.height,
+            count,
+            slices,
+            |layer, depth| surface.get(layer, depth, 0),
+        )
+    }
+
+    /// Decode the base mipmap of each array layer, cube map face, or depth slice to floating point.
+    /// Depth slices use the first layer.
+    pub fn to_slice_images_f32(&self, slices: Slices) -> Result<Vec<Rgba32FImage>, Error> {
+        let surface = self.decode_rgbaf32()?;
+        let count = slices.count(surface.layers, surface.depth);
+        slice_images(
+            surface.width,
+            surface.height,
+            count,
+            slices,
+            |layer, depth| surface.get(layer, depth, 0),
+        )
+    }
+
+    fn decode_rgba8(&self) -> Result<SurfaceRgba8<Vec<u8>>, Error> {
+        match self {
+            ImageFile::Image(surface) => Ok(surface.clone()),
+            ImageFile::ImageFloat(surface) => Ok(SurfaceRgba8 {
+                width: surface.width,
+                height: surface.height,
+                depth: surface.depth,
+                layers: surface.layers,
+                mipmaps: surface.mipmaps,
+                data: surface
+                    .data
+                    .iter()
+                    .map(|f| (f.clamp(0.0, 1.0) * 255.0).round() as u8)
+                    .collect(),
+            }),
+            ImageFile::Dds(dds) => decode_rgba8(dds),
+            ImageFile::Nutexb(nutexb) => decode_rgba8(&nutexb_to_dds(nutexb)?),
+            ImageFile::Bntx { bntx, .. } => decode_rgba8(&bntx_to_dds(bntx)?),
+        }
+    }
+
+    fn decode_rgbaf32(&self) -> Result<SurfaceRgba32Float<Vec<f32>>, Error> {
+        match self {
+            ImageFile::Image(surface) => Ok(SurfaceRgba32Float {
+                width: surface.width,
+                height: surface.height,
+                depth: surface.depth,
+                layers: surface.layers,
+                mipmaps: surface.mipmaps,
+                data: surface.data.iter().map(|u| *u as f32 / 255.0).collect(),
+            }),
+            ImageFile::ImageFloat(surface) => Ok(surface.clone()),
+            ImageFile::Dds(dds) => decode_rgbaf32(dds),
+            ImageFile::Nutexb(nutexb) => decode_rgbaf32(&nutexb_to_dds(nutexb)?),
+            ImageFile::Bntx { bntx, .. } => decode_rgbaf32(&bntx_to_dds(bntx)?),
+        }
+    }
+
+    /// Save the base mipmap to an image format supported by the image crate.
+    ///
+    /// EXR and Radiance HDR files preserve floating point values.
+    /// PNG and TIFF files use 16-bit or floating point channels for floating point formats.
+    pub fn save_image(&self, output: &Path) -> Result<(), Error> {
+        let format = image_dds::image::ImageFormat::from_path(output)?;
+        save_to_file(output, |writer| self.write_image_to(writer, format))
+    }
+
+    /// Write the base mipmap to `writer` using an image format supported by the image crate.
+    ///
+    /// EXR and Radiance HDR files preserve floating point values.
+    /// PNG and TIFF files use 16-bit or floating point channels for floating point formats.
+    pub fn write_image_to<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        format: image_dds::image::ImageFormat,
+    ) -> Result<(), Error> {
+        let image = if self.needs_float_image(format) {
+            self.to_image_f32()?.into()
+        } else {
+            self.to_image()?.into()
+        };
+        write_dynamic_image(image, writer, format)
+    }
+
+    /// Save each array layer, cube map face, or depth slice to a separate image
+    /// and return the saved paths.
+    ///
+    /// Files with multiple slices add a suffix to the file name from [layer_path] or [depth_slice_path].
+    pub fn save_slice_images(&self, output: &Path, slices: Slices) -> Result<Vec<PathBuf>, Error> {
+        let format = image_dds::image::ImageFormat::from_path(output)?;
+        self.slice_images(output, slices, format)?
+            .into_iter()
+            .map(|(path, image)| {
+                save_to_file(&path, |writer| write_dynamic_image(image, writer, format))?;
+                Ok(path)
+            })
+            .collect()
+    }
+
+    fn slice_images(
+        &self,
+        output: &Path,
+        slices: Slices,
+        format: image_dds::image::ImageFormat,
+    ) -> Result<Vec<(PathBuf, DynamicImage)>, Error> {
+        let images: Vec<DynamicImage> = if self.needs_float_image(format) {
+            let images = self.to_slice_images_f32(slices)?;
+            images.into_iter().map(Into::into).collect()
+        } else {
+            let images = self.to_slice_images(slices)?;
+            images.into_iter().map(Into::into).collect()
+        };
+        let count = images.len() as u32;
+        Ok(images
+            .into_iter()
+            .zip(0..)
+            .map(|(image, i)| {
+                let path = if count > 1 {
+                    slices.path(output, i, count)
+                } else {
+                    output.to_path_buf()
+                };
+                (path, image)
+            })
+            .collect())
+    }
+
+    /// Save the depth slices of a 3D texture to a single image with slices
+    /// tiled left to right and top to bottom with `columns` slices per row.
+    pub fn save_depth_atlas(&self, output: &Path, columns: u32) -> Result<(), Error> {
+        let format = image_dds::image::ImageFormat::from_path(output)?;
+        let image: DynamicImage = if self.needs_float_image(format) {
+            tile_images(&self.to_slice_images_f32(Slices::Depth)?, columns).into()
+        } else {
+            tile_images(&self.to_slice_images(Slices::Depth)?, columns).into()
+        };
+        save_to_file(output, |writer| write_dynamic_image(image, writer, format))
+    }
+
+    fn needs_float_image(&self, format: image_dds::image::ImageFormat) -> bool {
+        match format {
+            image_dds::image::ImageFormat::OpenExr | image_dds::image::ImageFormat::Hdr => true,
+            image_dds::image::ImageFormat::Png | image_dds::image::ImageFormat::Tiff => {
+                matches!(self.image_format(), Ok(format) if is_float_format(format))
             }
+            _ => false,
         }
     }
 
-    pub fn save_image(&self, output: &Path) -> anyhow::Result<()> {
-        self.to_image()?.save(output).map_err(Into::into)
+    /// The container for the file data.
+    pub fn container(&self) -> Container {
+        match self {
+            ImageFile::Image(_) | ImageFile::ImageFloat(_) => Container::Image,
+            ImageFile::Dds(_) => Container::Dds,
+            ImageFile::Nutexb(_) => Container::Nutexb,
+            ImageFile::Bntx { .. } => Container::Bntx,
+        }
     }
 
+    /// The name stored in the file for nutexb and bntx files.
+    pub fn internal_name(&self) -> Option<String> {
+        match self {
+            ImageFile::Nutexb(nutexb) => Some(nutexb.footer.string.to_string()),
+            ImageFile::Bntx { name, .. } => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    /// The internal name when saving to `output` without specifying a name.
+    ///
+    /// Nutexb and bntx files keep their existing internal name, and other files use the file name of `output`.
+    pub fn default_internal_name(&self, output: &Path) -> String {
+        self.internal_name()
+            .unwrap_or_else(|| file_name_no_extension(output))
+    }
+
+    /// Save a nutexb file with `name` as the internal name
+    /// or [ImageFile::default_internal_name] if `name` is `None`.
     pub fn save_nutexb(
         &self,
         output: &Path,
+        name: Option<&str>,
         image_format: image_dds::ImageFormat,
         quality: image_dds::Quality,
         mipmaps: image_dds::Mipmaps,
-    ) -> anyhow::Result<()> {
-        // Nutexb files use the file name as the internal name.
-        let name = output
-            .with_extension("")
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
+    ) -> Result<(), Error> {
+        let name = name
+            .map(str::to_string)
+            .unwrap_or_else(|| self.default_internal_name(output));
+        save_to_file(output, |writer| {
+            self.write_nutexb_to(writer, &name, image_format, quality, mipmaps)
+        })
+    }
 
-        // Use image_dds to encode to a new format if necessary.
-        match self {
-            ImageFile::Image(image) => {
-                let dds = image_dds::dds_from_image(image, image_format, quality, mipmaps)?;
-                let nutexb = NutexbFile::from_dds(&dds, name)?;
-                nutexb.write_to_file(output)?;
-            }
-            ImageFile::Dds(dds) => {
-                let new_dds = encode_dds(dds, image_format, quality, mipmaps)?;
-                let nutexb = NutexbFile::from_dds(&new_dds, name)?;
-                nutexb.write_to_file(output)?;
-            }
-            ImageFile::Nutexb(nutexb) => {
-                let dds = nutexb.to_dds()?;
-                let new_dds = encode_dds(&dds, image_format, quality, mipmaps)?;
-                let new_nutexb = NutexbFile::from_dds(&new_dds, nutexb.footer.string.to_string())?;
-                new_nutexb.write_to_file(output)?;
-            }
-            ImageFile::Bntx(bntx) => {
-                let dds = bntx.to_dds()?;
-                let new_dds = encode_dds(&dds, image_format, quality, mipmaps)?;
-                let nutexb = NutexbFile::from_dds(&new_dds, name)?;
-                nutexb.write_to_file(output)?;
-            }
-        }
+    /// Write a nutexb file to `writer` with `name` as the internal name.
+    pub fn write_nutexb_to<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        name: &str,
+        image_format: image_dds::ImageFormat,
+        quality: image_dds::Quality,
+        mipmaps: image_dds::Mipmaps,
+    ) -> Result<(), Error> {
+        let dds = self.encode(image_format, quality, mipmaps)?;
+        let nutexb = NutexbFile::from_dds(&dds, name.to_string()).map_err(Error::encode)?;
+        nutexb.write(writer).map_err(Error::write)?;
         Ok(())
     }
 
+    /// Save a bntx file with `name` as the internal name
+    /// or [ImageFile::default_internal_name] if `name` is `None`.
     pub fn save_bntx(
         &self,
         output: &Path,
+        name: Option<&str>,
         image_format: image_dds::ImageFormat,
         quality: image_dds::Quality,
         mipmaps: image_dds::Mipmaps,
-    ) -> anyhow::Result<()> {
-        // Nutexb files use the file name as the internal name.
-        let name = output
-            .with_extension("")
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
+    ) -> Result<(), Error> {
+        let name = name
+            .map(str::to_string)
+            .unwrap_or_else(|| self.default_internal_name(output));
+        save_to_file(output, |writer| {
+            self.write_bntx_to(writer, &name, image_format, quality, mipmaps)
+        })
+    }
 
-        match self {
-            ImageFile::Image(image) => {
-                let dds = image_dds::dds_from_image(image, image_format, quality, mipmaps)?;
-                let bntx = Bntx::from_dds(&dds, &name)?;
-                bntx.save(output)?;
-            }
-            ImageFile::Dds(dds) => {
-                let new_dds = encode_dds(dds, image_format, quality, mipmaps)?;
-                let bntx = Bntx::from_dds(&new_dds, &name)?;
-                bntx.save(output)?;
-            }
-            ImageFile::Nutexb(nutexb) => {
-                let dds = nutexb.to_dds()?;
-                let new_dds = encode_dds(&dds, image_format, quality, mipmaps)?;
-                let bntx = Bntx::from_dds(&new_dds, &name)?;
-                bntx.save(output)?;
-            }
-            ImageFile::Bntx(bntx) => {
-                let dds = bntx.to_dds()?;
-                let new_dds = encode_dds(&dds, image_format, quality, mipmaps)?;
-                let bntx = Bntx::from_dds(&new_dds, &name)?;
-                bntx.save(output)?;
-            }
-        }
+    /// Write a bntx file to `writer` with `name` as the internal name.
+    pub fn write_bntx_to<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        name: &str,
+        image_format: image_dds::ImageFormat,
+        quality: image_dds::Quality,
+        mipmaps: image_dds::Mipmaps,
+    ) -> Result<(), Error> {
+        let dds = self.encode(image_format, quality, mipmaps)?;
+        let bntx = Bntx::from_dds(&dds, name).map_err(Error::encode)?;
+        bntx.write(writer).map_err(Error::write)?;
         Ok(())
     }
 
@@ -176,48 +612,341 @@ impl ImageFile {
         image_format: image_dds::ImageFormat,
         quality: image_dds::Quality,
         mipmaps: image_dds::Mipmaps,
-    ) -> anyhow::Result<()> {
+    ) -> Result<(), Error> {
+        save_to_file(output, |writer| {
+            self.write_dds_to(writer, image_format, quality, mipmaps)
+        })
+    }
+
+    pub fn write_dds_to<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        image_format: image_dds::ImageFormat,
+        quality: image_dds::Quality,
+        mipmaps: image_dds::Mipmaps,
+    ) -> Result<(), Error> {
+        let dds = self.encode(image_format, quality, mipmaps)?;
+        dds.write(writer).map_err(Error::write)?;
+        Ok(())
+    }
+
+    fn encode(
+        &self,
+        image_format: ImageFormat,
+        quality: image_dds::Quality,
+        mipmaps: image_dds::Mipmaps,
+    ) -> Result<Dds, Error> {
+        // Use image_dds to encode to a new format if necessary.
         match self {
-            ImageFile::Image(image) => {
-                let dds = image_dds::dds_from_image(image, image_format, quality, mipmaps)?;
-                write_dds(output, &dds)?;
-            }
-            ImageFile::Dds(dds) => {
-                let new_dds = encode_dds(dds, image_format, quality, mipmaps)?;
-                write_dds(output, &new_dds)?;
-            }
+            ImageFile::Image(surface) => surface
+                .encode(image_format, quality, mipmaps)
+                .map_err(Error::encode)?
+                .to_dds()
+                .map_err(Error::encode),
+            ImageFile::ImageFloat(surface) => surface
+                .encode(image_format, quality, mipmaps)
+                .map_err(Error::encode)?
+                .to_dds()
+                .map_err(Error::encode),
+            ImageFile::Dds(dds) => encode_dds(dds, image_format, quality, mipmaps),
             ImageFile::Nutexb(nutexb) => {
-                let dds = nutexb.to_dds()?;
-                let new_dds = encode_dds(&dds, image_format, quality, mipmaps)?;
-                write_dds(output, &new_dds)?;
+                let dds = nutexb_to_dds(nutexb)?;
+                encode_dds(&dds, image_format, quality, mipmaps)
             }
-            ImageFile::Bntx(bntx) => {
-                let dds = bntx.to_dds()?;
-                let new_dds = encode_dds(&dds, image_format, quality, mipmaps)?;
-                write_dds(output, &new_dds)?;
+            ImageFile::Bntx { bntx, .. } => {
+                let dds = bntx_to_dds(bntx)?;
+                encode_dds(&dds, image_format, quality, mipmaps)
             }
+        }
+    }
+}
+
+/// Array layers or depth slices when converting textures to or from separate images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slices {
+    /// Array layers or cube map faces named using [layer_path].
+    Layers,
+    /// Depth slices of 3D textures named using [depth_slice_path].
+    Depth,
+}
+
+impl Slices {
+    fn count(self, layers: u32, depth: u32) -> u32 {
+        match self {
+            Slices::Layers => layers,
+            Slices::Depth => depth,
+        }
+    }
+
+    fn layers_and_depth(self, count: u32) -> (u32, u32) {
+        match self {
+            Slices::Layers => (count, 1),
+            Slices::Depth => (1, count),
+        }
+    }
+
+    fn layer_and_depth_level(self, slice: u32) -> (u32, u32) {
+        match self {
+            Slices::Layers => (slice, 0),
+            Slices::Depth => (0, slice),
+        }
+    }
+
+    fn path(self, path: &Path, slice: u32, count: u32) -> PathBuf {
+        match self {
+            Slices::Layers => layer_path(path, slice, count),
+            Slices::Depth => depth_slice_path(path, slice),
+        }
+    }
+}
+
+/// The suffix for `layer` when saving or loading layered textures as separate images.
+///
+/// Cube maps with 6 faces use [CUBE_FACE_SUFFIXES], and other layered textures use the layer index.
+pub fn layer_suffix(layer: u32, layer_count: u32) -> String {
+    if layer_count == 6 {
+        CUBE_FACE_SUFFIXES[layer as usize].to_string()
+    } else {
+        layer.to_string()
+    }
+}
+
+/// Group paths like `spec_px.png` and `spec_nx.png` that use the naming convention from [layer_path].
+///
+/// Each item contains the path without the layer suffix and the paths for each layer in order.
+/// Six layers can use either [CUBE_FACE_SUFFIXES] or the layer indices `0..6`.
+/// Paths that are not part of a complete set of cube map faces or layers `0..N` have no layer paths.
+/// Only image files like PNG are grouped since [ImageFile::from_slice_files] only supports images.
+pub fn group_layer_files(paths: Vec<PathBuf>) -> Vec<(PathBuf, Vec<PathBuf>)> {
+    // Preserve the original order using the first path for each group.
+    let mut groups: Vec<(Option<PathBuf>, Vec<(String, PathBuf)>)> = Vec::new();
+    for path in paths {
+        let layer_suffix = if Container::from_path(&path) == Container::Image {
+            split_layer_suffix(&path)
+        } else {
+            None
         };
+        match layer_suffix {
+            Some((base, suffix)) => {
+                match groups.iter_mut().find(|(b, _)| b.as_ref() == Some(&base)) {
+                    Some((_, layers)) => layers.push((suffix, path)),
+                    None => groups.push((Some(base), vec![(suffix, path)])),
+                }
+            }
+            None => groups.push((None, vec![(String::new(), path)])),
+        }
+    }
 
-        Ok(())
+    groups
+        .into_iter()
+        .flat_map(|(base, mut layers)| {
+            let suffixes = layer_suffix_order(&layers);
+            match (base, suffixes) {
+                (Some(base), Some(suffixes)) => {
+                    layers.sort_by_key(|(s, _)| suffixes.iter().position(|suffix| suffix == s));
+                    vec![(base, layers.into_iter().map(|(_, p)| p).collect())]
+                }
+                _ => layers.into_iter().map(|(_, p)| (p, Vec::new())).collect(),
+            }
+        })
+        .collect()
+}
+
+// The suffixes in layer order if the layers are a complete set of cube map faces or indices.
+fn layer_suffix_order(layers: &[(String, PathBuf)]) -> Option<Vec<String>> {
+    let layer_count = layers.len() as u32;
+    if layer_count <= 1 {
+        return None;
     }
+
+    let indices: Vec<_> = (0..layer_count).map(|i| i.to_string()).collect();
+    let conventions = if layer_count == 6 {
+        vec![CUBE_FACE_SUFFIXES.map(str::to_string).to_vec(), indices]
+    } else {
+        vec![indices]
+    };
+    conventions.into_iter().find(|suffixes| {
+        suffixes
+            .iter()
+            .all(|suffix| layers.iter().filter(|(s, _)| s == suffix).count() == 1)
+    })
+}
+
+fn split_layer_suffix(path: &Path) -> Option<(PathBuf, String)> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let (name, suffix) = stem.rsplit_once('_')?;
+    let is_layer_suffix = CUBE_FACE_SUFFIXES.contains(&suffix)
+        || (!suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit()));
+    if !is_layer_suffix {
+        return None;
+    }
+
+    let base = match path.extension() {
+        Some(ext) => path.with_file_name(format!("{name}.{}", ext.to_string_lossy())),
+        None => path.with_file_name(name),
+    };
+    Some((base, suffix.to_string()))
+}
+
+/// The path for `layer` like `spec_px.png` or `eye_1.png`.
+pub fn layer_path(path: &Path, layer: u32, layer_count: u32) -> PathBuf {
+    path_with_suffix(path, &layer_suffix(layer, layer_count))
+}
+
+/// The path for the depth slice `slice` of a 3D texture like `lut_z0.png`.
+///
+/// This uses a different suffix than [layer_path] to avoid loading depth slices as array layers.
+pub fn depth_slice_path(path: &Path, slice: u32) -> PathBuf {
+    path_with_suffix(path, &format!("z{slice}"))
+}
+
+/// The path for a difference heatmap like `def_mario_001_col_diff.png`.
+pub fn heatmap_path(path: &Path) -> PathBuf {
+    path_with_suffix(path, "diff").with_extension("png")
+}
+
+/// Returns `true` if the internal `name` for a nutexb or bntx file
+/// matches the file name of `path` without the extension.
+pub fn internal_name_matches_file_name(name: &str, path: &Path) -> bool {
+    name == file_name_no_extension(path)
+}
+
+/// The path for a single `channel` like `def_mario_001_prm_r.png`.
+pub fn channel_path(path: &Path, channel: Channel) -> PathBuf {
+    path_with_suffix(path, &channel.to_string().to_lowercase())
+}
+
+fn path_with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(ext) => path.with_file_name(format!("{stem}_{suffix}.{}", ext.to_string_lossy())),
+        None => path.with_file_name(format!("{stem}_{suffix}")),
+    }
+}
+
+// A file opened for reading with the container detected from the contents or extension.
+enum FileReader {
+    Container(BufReader<std::fs::File>, Container),
+    Image(ImageReader<BufReader<std::fs::File>>),
+}
+
+fn open_file(path: &Path) -> Result<FileReader, Error> {
+    let mut reader = BufReader::new(std::fs::File::open(path)?);
+
+    // Check the file contents first in case the extension is incorrect.
+    let container = match Container::from_contents(&mut reader)? {
+        Some(container) => container,
+        None => Container::from_path(path),
+    };
+
+    match container {
+        Container::Image => {
+            let format = image_dds::image::ImageFormat::from_path(path).ok();
+            Ok(FileReader::Image(image_reader(reader, format)?))
+        }
+        container => Ok(FileReader::Container(reader, container)),
+    }
+}
+
+fn image_reader<R: BufRead + Seek>(
+    reader: R,
+    format: Option<image_dds::image::ImageFormat>,
+) -> Result<ImageReader<R>, Error> {
+    let mut image = ImageReader::new(reader).with_guessed_format()?;
+    if image.format().is_none() {
+        // Use the hint for image formats like TGA without magic bytes.
+        if let Some(format) = format {
+            image.set_format(format);
+        }
+    }
+    Ok(image)
+}
+
+fn detect_container<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+) -> std::io::Result<Option<Container>> {
+    let mut header = Vec::new();
+    reader.by_ref().take(32).read_to_end(&mut header)?;
+
+    if header.starts_with(b"DDS ") {
+        return Ok(Some(Container::Dds));
+    }
+    if header.starts_with(b"BNTX") {
+        return Ok(Some(Container::Bntx));
+    }
+
+    // Nutexb files start with image data, so check the magic in the footer.
+    let end = reader.seek(SeekFrom::End(0))?;
+    if end - start >= NUTEXB_FOOTER_SIZE {
+        let mut footer = [0u8; NUTEXB_FOOTER_SIZE as usize];
+        reader.seek(SeekFrom::Start(end - NUTEXB_FOOTER_SIZE))?;
+        reader.read_exact(&mut footer)?;
+        if footer.starts_with(b" XNT") || footer[0x68..0x6C] == *b" XET" {
+            return Ok(Some(Container::Nutexb));
+        }
+    }
+
+    if image_dds::image::guess_format(&header).is_ok() {
+        return Ok(Some(Container::Image));
+    }
+
+    Ok(None)
+}
+
+fn dds_layer_count(dds: &Dds) -> u32 {
+    // Use image_dds to also count faces for cube maps with a DX10 header.
+    Surface::from_dds(dds).map(|s| s.layers).unwrap_or(1)
 }
 
 fn fix_mipmap_count(nutexb: &mut NutexbFile) {
-    let max_mipmaps = nutexb
-        .footer
-        .width
-        .max(nutexb.footer.height)
-        .max(nutexb.footer.depth)
-        .ilog2();
+    let max_mipmaps = full_mipmap_count(
+        nutexb.footer.width,
+        nutexb.footer.height,
+        nutexb.footer.depth,
+    );
     nutexb.footer.mipmap_count = nutexb.footer.mipmap_count.min(max_mipmaps);
 }
 
-fn bntx_image_format(bntx: &Bntx) -> ImageFormat {
-    bntx.image_format().try_into().unwrap()
+// The number of mipmaps including the base level down to 1x1x1.
+fn full_mipmap_count(width: u32, height: u32, depth: u32) -> u32 {
+    width.max(height).max(depth).max(1).ilog2() + 1
+}
+
+fn bntx_image_format(bntx: &Bntx) -> Result<ImageFormat, Error> {
+    ImageFormat::try_from(bntx.image_format()).map_err(|e| Error::UnsupportedFormat {
+        container: Container::Bntx,
+        reason: format!("{e:?}"),
+    })
+}
+
+fn nutexb_to_dds(nutexb: &NutexbFile) -> Result<Dds, Error> {
+    nutexb
+        .to_dds()
+        .map_err(Error::invalid_container(Container::Nutexb))
 }
 
-fn nutexb_image_format(nutexb: &NutexbFile) -> ImageFormat {
-    match nutexb.footer.image_format {
+fn bntx_to_dds(bntx: &Bntx) -> Result<Dds, Error> {
+    bntx.to_dds()
+        .map_err(Error::invalid_container(Container::Bntx))
+}
+
+fn decode_rgba8(dds: &Dds) -> Result<SurfaceRgba8<Vec<u8>>, Error> {
+    Surface::from_dds(dds)
+        .map_err(Error::decode)?
+        .decode_rgba8()
+        .map_err(Error::decode)
+}
+
+fn decode_rgbaf32(dds: &Dds) -> Result<SurfaceRgba32Float<Vec<f32>>, Error> {
+    Surface::from_dds(dds)
+        .map_err(Error::decode)?
+        .decode_rgbaf32()
+        .map_err(Error::decode)
+}
+
+fn nutexb_image_format(format: nutexb::NutexbFormat) -> ImageFormat {
+    match format {
         nutexb::NutexbFormat::R8Unorm => ImageFormat::R8Unorm,
         nutexb::NutexbFormat::R8G8B8A8Unorm => ImageFormat::Rgba8Unorm,
         nutexb::NutexbFormat::R8G8B8A8Srgb => ImageFormat::Rgba8UnormSrgb,
@@ -246,28 +975,632 @@ fn encode_dds(
     image_format: ImageFormat,
     quality: image_dds::Quality,
     mipmaps: image_dds::Mipmaps,
-) -> anyhow::Result<Dds> {
+) -> Result<Dds, Error> {
     if matches!(dds_image_format(dds), Ok(format) if format == image_format) {
         // Avoid lossy conversions if the format doesn't change.
-        // TODO: Handle different mipmap counts.
-        // Dds does not implement Clone, so we need to get creative.
-        let mut writer = std::io::Cursor::new(Vec::new());
-        dds.write(&mut writer)?;
-        let mut reader = std::io::Cursor::new(writer.into_inner());
-        Dds::read(&mut reader).map_err(Into::into)
+        let surface = Surface::from_dds(dds).map_err(Error::decode)?;
+        let full_count = full_mipmap_count(surface.width, surface.height, surface.depth);
+        let mipmap_count = match mipmaps {
+            Mipmaps::Disabled => 1,
+            Mipmaps::GeneratedExactly(count) => count.clamp(1, full_count),
+            // Keep existing mipmaps but generate mipmaps for textures without any.
+            Mipmaps::GeneratedAutomatic if surface.mipmaps == 1 => full_count,
+            _ => surface.mipmaps,
+        };
+
+        if mipmap_count < surface.mipmaps {
+            // Removing mipmaps doesn't require decoding the remaining mipmaps.
+            return truncate_mipmaps(&surface, mipmap_count)?
+                .to_dds()
+                .map_err(Error::encode);
+        } else if mipmap_count == surface.mipmaps {
+            // Dds does not implement Clone, so we need to get creative.
+            let mut writer = std::io::Cursor::new(Vec::new());
+            dds.write(&mut writer).map_err(Error::write)?;
+            let mut reader = std::io::Cursor::new(writer.into_inner());
+            return Dds::read(&mut reader).map_err(Error::invalid_container(Container::Dds));
+        }
+    }
+
+    // Changing the format or generating additional mipmaps requires encoding again.
+    if is_float_format(image_format)
+        || matches!(dds_image_format(dds), Ok(format) if is_float_format(format))
+    {
+        // Decode to floating point to avoid clamping HDR values.
+        // All mipmaps are decoded, so Mipmaps::FromSurface preserves custom mipmaps.
+        decode_rgbaf32(dds)?
+            .encode(image_format, quality, mipmaps)
+            .map_err(Error::encode)?
+            .to_dds()
+            .map_err(Error::encode)
     } else {
         // Decode and encode to the desired format.
         // This also handles adjusting the number of mipmaps.
-        Surface::from_dds(dds)?
-            .decode_rgba8()?
-            .encode(image_format, quality, mipmaps)?
+        // All mipmaps are decoded, so Mipmaps::FromSurface preserves custom mipmaps.
+        decode_rgba8(dds)?
+            .encode(image_format, quality, mipmaps)
+            .map_err(Error::encode)?
             .to_dds()
-            .map_err(Into::into)
+            .map_err(Error::encode)
     }
 }
 
-fn write_dds(output: &Path, dds: &Dds) -> anyhow::Result<()> {
-    let mut writer = std::io::BufWriter::new(std::fs::File::create(output)?);
-    dds.write(&mut writer)?;
+fn rgba8_surface(image: RgbaImage) -> SurfaceRgba8<Vec<u8>> {
+    SurfaceRgba8 {
+        width: image.width(),
+        height: image.height(),
+        depth: 1,
+        layers: 1,
+        mipmaps: 1,
+        data: image.into_raw(),
+    }
+}
+
+fn rgbaf32_surface(image: Rgba32FImage) -> SurfaceRgba32Float<Vec<f32>> {
+    SurfaceRgba32Float {
+        width: image.width(),
+        height: image.height(),
+        depth: 1,
+        layers: 1,
+        mipmaps: 1,
+        data: image.into_raw(),
+    }
+}
+
+fn is_float_format(format: ImageFormat) -> bool {
+    matches!(
+        format,
+        ImageFormat::BC6hRgbUfloat
+            | ImageFormat::BC6hRgbSfloat
+            | ImageFormat::Rgba16Float
+            | ImageFormat::Rgba32Float
+    )
+}
+
+pub(crate) fn is_high_precision(image: &DynamicImage) -> bool {
+    is_high_precision_color(image.color())
+}
+
+fn is_high_precision_color(color: ColorType) -> bool {
+    color.bits_per_pixel() / color.channel_count() as u16 > 8
+}
+
+fn combine_layers<P: Pixel>(
+    images: Vec<ImageBuffer<P, Vec<P::Subpixel>>>,
+) -> Result<(u32, u32, u32, Vec<P::Subpixel>), Error> {
+    let (width, height) = images
+        .first()
+        .map(|i| i.dimensions())
+        .ok_or(Error::MissingLayers)?;
+    if let Some(i) = images
+        .iter()
+        .position(|i| i.dimensions() != (width, height))
+    {
+        return Err(Error::LayerDimensions {
+            layer: i,
+            actual: images[i].dimensions(),
+            expected: (width, height),
+        });
+    }
+
+    let layers = images.len() as u32;
+    let data = images.into_iter().flat_map(|i| i.into_raw()).collect();
+    Ok((width, height, layers, data))
+}
+
+fn slice_images<'a, P: Pixel + 'a>(
+    width: u32,
+    height: u32,
+    count: u32,
+    slices: Slices,
+    get: impl Fn(u32, u32) -> Option<&'a [P::Subpixel]>,
+) -> Result<Vec<ImageBuffer<P, Vec<P::Subpixel>>>, Error> {
+    let name = match slices {
+        Slices::Layers => "layer",
+        Slices::Depth => "depth slice",
+    };
+    (0..count)
+        .map(|i| {
+            let (layer, depth_level) = slices.layer_and_depth_level(i);
+            let data = get(layer, depth_level)
+                .ok_or_else(|| Error::decode(format!("Failed to get data for {name} {i}")))?;
+            ImageBuffer::from_raw(width, height, data.to_vec())
+                .ok_or_else(|| Error::decode(format!("Failed to create image for {name} {i}")))
+        })
+        .collect()
+}
+
+fn tile_images<P: Pixel>(
+    images: &[ImageBuffer<P, Vec<P::Subpixel>>],
+    columns: u32,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let (width, height) = images.first().map(|i| i.dimensions()).unwrap_or_default();
+    let columns = columns.clamp(1, images.len().max(1) as u32);
+    let rows = (images.len() as u32).div_ceil(columns);
+
+    let mut atlas = ImageBuffer::new(width * columns, height * rows);
+    for (i, image) in images.iter().enumerate() {
+        let (x, y) = (i as u32 % columns, i as u32 / columns);
+        imageops::replace(&mut atlas, image, (x * width) as i64, (y * height) as i64);
+    }
+    atlas
+}
+
+fn split_atlas<P: Pixel + 'static>(
+    atlas: &ImageBuffer<P, Vec<P::Subpixel>>,
+    depth: u32,
+    columns: u32,
+) -> Result<Vec<ImageBuffer<P, Vec<P::Subpixel>>>, Error> {
+    let (width, height) = atlas.dimensions();
+    let invalid_dimensions = Error::AtlasDimensions {
+        width,
+        height,
+        depth,
+        columns,
+    };
+    if depth == 0 || columns == 0 {
+        return Err(invalid_dimensions);
+    }
+
+    let rows = depth.div_ceil(columns);
+    if width % columns != 0 || height % rows != 0 {
+        return Err(invalid_dimensions);
+    }
+
+    let (slice_width, slice_height) = (width / columns, height / rows);
+    Ok((0..depth)
+        .map(|i| {
+            let (x, y) = (i % columns, i / columns);
+            imageops::crop_imm(
+                atlas,
+                x * slice_width,
+                y * slice_height,
+                slice_width,
+                slice_height,
+            )
+            .to_image()
+        })
+        .collect())
+}
+
+pub(crate) fn write_dynamic_image<W: Write + Seek>(
+    image: DynamicImage,
+    writer: &mut W,
+    format: image_dds::image::ImageFormat,
+) -> Result<(), Error> {
+    let image = match format {
+        // Radiance HDR files don't support alpha.
+        image_dds::image::ImageFormat::Hdr => image.to_rgb32f().into(),
+        image_dds::image::ImageFormat::OpenExr => image.to_rgba32f().into(),
+        // PNG files support at most 16 bits per channel.
+        image_dds::image::ImageFormat::Png if image.color() == ColorType::Rgba32F => {
+            image.to_rgba16().into()
+        }
+        _ => image,
+    };
+    image.write_to(writer, format)?;
+    Ok(())
+}
+
+pub(crate) fn save_to_file<F>(output: &Path, write: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut Cursor<Vec<u8>>) -> Result<(), Error>,
+{
+    // Write to memory first to avoid leaving partially written files on errors.
+    let mut writer = Cursor::new(Vec::new());
+    write(&mut writer)?;
+    std::fs::write(output, writer.into_inner())?;
     Ok(())
 }
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase()
+}
+
+fn file_name_no_extension(path: &Path) -> String {
+    path.with_extension("")
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+fn truncate_mipmaps(surface: &Surface<&[u8]>, mipmaps: u32) -> Result<Surface<Vec<u8>>, Error> {
+    // Copy the existing data for each layer and depth slice of the remaining mipmaps.
+    let mut data = Vec::new();
+    for layer in 0..surface.layers {
+        for mipmap in 0..mipmaps {
+            for depth_level in 0..(surface.depth >> mipmap).max(1) {
+                let mip_data = surface.get(layer, depth_level, mipmap).ok_or_else(|| {
+                    Error::decode(format!(
+                        "Failed to get data for layer {layer} mipmap {mipmap}"
+                    ))
+                })?;
+                data.extend_from_slice(mip_data);
+            }
+        }
+    }
+
+    Ok(Surface {
+        width: surface.width,
+        height: surface.height,
+        depth: surface.depth,
+        layers: surface.layers,
+        mipmaps,
+        image_format: surface.image_format,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba8_surface_with_mipmaps(layers: u32, mipmaps: u32) -> Surface<Vec<u8>> {
+        // 4x4, 2x2, and 1x1 mipmaps for each layer with unique byte values.
+        let layer_size: usize = [64, 16, 4][..mipmaps as usize].iter().sum();
+        Surface {
+            width: 4,
+            height: 4,
+            depth: 1,
+            layers,
+            mipmaps,
+            image_format: ImageFormat::Rgba8Unorm,
+            data: (0..layer_size * layers as usize).map(|i| i as u8).collect(),
+        }
+    }
+
+    #[test]
+    fn truncate_mipmaps_copies_data() {
+        let surface = rgba8_surface_with_mipmaps(2, 3);
+        let surface = Surface {
+            width: surface.width,
+            height: surface.height,
+            depth: surface.depth,
+            layers: surface.layers,
+            mipmaps: surface.mipmaps,
+            image_format: surface.image_format,
+            data: surface.data.as_slice(),
+        };
+        let truncated = truncate_mipmaps(&surface, 2).unwrap();
+
+        assert_eq!(2, truncated.layers);
+        assert_eq!(2, truncated.mipmaps);
+        assert_eq!(ImageFormat::Rgba8Unorm, truncated.image_format);
+        // The removed 1x1 mipmap is at the end of each layer.
+        let expected: Vec<_> = (0..80).chain(84..164).map(|i| i as u8).collect();
+        assert_eq!(expected, truncated.data);
+    }
+
+    #[test]
+    fn encode_dds_removes_mipmaps_without_reencoding() {
+        let surface = rgba8_surface_with_mipmaps(1, 3);
+        let dds = surface.to_dds().unwrap();
+
+        let output = encode_dds(
+            &dds,
+            ImageFormat::Rgba8Unorm,
+            image_dds::Quality::Fast,
+            Mipmaps::GeneratedExactly(2),
+        )
+        .unwrap();
+
+        let output = Surface::from_dds(&output).unwrap();
+        assert_eq!(2, output.mipmaps);
+        assert_eq!(&surface.data[..80], output.data);
+    }
+
+    #[test]
+    fn encode_dds_generates_additional_mipmaps() {
+        let surface = rgba8_surface_with_mipmaps(1, 1);
+        let dds = surface.to_dds().unwrap();
+
+        let output = encode_dds(
+            &dds,
+            ImageFormat::Rgba8Unorm,
+            image_dds::Quality::Fast,
+            Mipmaps::GeneratedExactly(3),
+        )
+        .unwrap();
+
+        let output = Surface::from_dds(&output).unwrap();
+        assert_eq!(3, output.mipmaps);
+        assert_eq!(&surface.data[..], &output.data[..64]);
+    }
+
+    #[test]
+    fn encode_dds_automatic_mipmaps_single_mipmap() {
+        let surface = rgba8_surface_with_mipmaps(1, 1);
+        let dds = surface.to_dds().unwrap();
+
+        let output = encode_dds(
+            &dds,
+            ImageFormat::Rgba8Unorm,
+            image_dds::Quality::Fast,
+            Mipmaps::GeneratedAutomatic,
+        )
+        .unwrap();
+
+        assert_eq!(3, Surface::from_dds(&output).unwrap().mipmaps);
+    }
+
+    #[test]
+    fn encode_dds_disabled_mipmaps_keeps_base_level() {
+        let surface = rgba8_surface_with_mipmaps(1, 3);
+        let dds = surface.to_dds().unwrap();
+
+        let output = encode_dds(
+            &dds,
+            ImageFormat::Rgba8Unorm,
+            image_dds::Quality::Fast,
+            Mipmaps::Disabled,
+        )
+        .unwrap();
+
+        let output = Surface::from_dds(&output).unwrap();
+        assert_eq!(1, output.mipmaps);
+        assert_eq!(&surface.data[..64], output.data);
+    }
+
+    #[test]
+    fn nutexb_automatic_mipmaps_are_valid() {
+        let image = ImageFile::Image(SurfaceRgba8 {
+            width: 4,
+            height: 4,
+            depth: 1,
+            layers: 1,
+            mipmaps: 1,
+            data: vec![128; 64],
+        });
+        let mut writer = Cursor::new(Vec::new());
+        image
+            .write_nutexb_to(
+                &mut writer,
+                "def_mario_001_col",
+                ImageFormat::Rgba8Unorm,
+                image_dds::Quality::Fast,
+                Mipmaps::GeneratedAutomatic,
+            )
+            .unwrap();
+
+        // Loading should keep the full mipmap chain down to 1x1.
+        let bytes = writer.into_inner();
+        let ImageFile::Nutexb(nutexb) = ImageFile::from_bytes(&bytes, Container::Nutexb).unwrap()
+        else {
+            panic!("expected a nutexb");
+        };
+        assert_eq!(3, nutexb.footer.mipmap_count);
+
+        let texture = TextureProperties {
+            container: Container::Nutexb,
+            format: ImageFormat::Rgba8Unorm,
+            dimensions: (4, 4, 1),
+            mipmap_count: Some(nutexb.footer.mipmap_count),
+            internal_name: Some("def_mario_001_col"),
+        };
+        assert!(validate(Path::new("def_mario_001_col.nutexb"), &texture, None).is_empty());
+    }
+
+    #[test]
+    fn bntx_internal_name() {
+        let dds = rgba8_surface_with_mipmaps(1, 1).to_dds().unwrap();
+        let mut writer = Cursor::new(Vec::new());
+        Bntx::from_dds(&dds, "chara_0_mario_01")
+            .unwrap()
+            .write(&mut writer)
+            .unwrap();
+
+        let image = ImageFile::from_bytes(&writer.into_inner(), Container::Bntx).unwrap();
+        assert_eq!(Some("chara_0_mario_01".to_string()), image.internal_name());
+    }
+
+    fn detect(bytes: Vec<u8>) -> Option<Container> {
+        Container::from_contents(&mut Cursor::new(bytes)).unwrap()
+    }
+
+    fn nutexb_bytes(magic_offset: usize, magic: &[u8]) -> Vec<u8> {
+        // Image data followed by the footer.
+        let mut bytes = vec![0u8; 0x100 + NUTEXB_FOOTER_SIZE as usize];
+        let footer = 0x100 + magic_offset;
+        bytes[footer..footer + 4].copy_from_slice(magic);
+        bytes
+    }
+
+    #[test]
+    fn detect_container_dds() {
+        let mut bytes = b"DDS ".to_vec();
+        bytes.resize(128, 0);
+        assert_eq!(Some(Container::Dds), detect(bytes));
+    }
+
+    #[test]
+    fn detect_container_bntx() {
+        let mut bytes = b"BNTX".to_vec();
+        bytes.resize(128, 0);
+        assert_eq!(Some(Container::Bntx), detect(bytes));
+    }
+
+    #[test]
+    fn detect_container_nutexb_footer() {
+        assert_eq!(Some(Container::Nutexb), detect(nutexb_bytes(0, b" XNT")));
+        assert_eq!(Some(Container::Nutexb), detect(nutexb_bytes(0x68, b" XET")));
+    }
+
+    #[test]
+    fn detect_container_png() {
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        bytes.resize(64, 0);
+        assert_eq!(Some(Container::Image), detect(bytes));
+    }
+
+    #[test]
+    fn detect_container_tga_uses_extension() {
+        // TGA files have no magic, so loading falls back to the extension.
+        let mut bytes = vec![0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 32, 8];
+        bytes.extend_from_slice(&[255; 4]);
+        assert_eq!(None, detect(bytes));
+        assert_eq!(Container::Image, Container::from_path("texture.tga"));
+    }
+
+    #[test]
+    fn image_from_reader_tga_format() {
+        let mut bytes = vec![0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 32, 8];
+        bytes.extend_from_slice(&[255; 4]);
+
+        let result = ImageFile::from_bytes(&bytes, Container::Image);
+        assert!(result.is_err());
+
+        let image = ImageFile::from_image_reader(
+            &mut Cursor::new(bytes),
+            Some(image_dds::image::ImageFormat::Tga),
+        )
+        .unwrap();
+        assert_eq!((1, 1, 1), image.dimensions());
+    }
+
+    #[test]
+    fn detect_container_restores_position() {
+        let mut bytes = vec![0u8; 8];
+        bytes.extend_from_slice(b"DDS ");
+        bytes.resize(128, 0);
+        let mut reader = Cursor::new(bytes);
+        reader.set_position(8);
+
+        assert_eq!(
+            Some(Container::Dds),
+            Container::from_contents(&mut reader).unwrap()
+        );
+        assert_eq!(8, reader.position());
+    }
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn group_layer_files_cube_faces() {
+        let files = paths(&[
+            "spec_nz.png",
+            "spec_px.png",
+            "spec_nx.png",
+            "spec_py.png",
+            "spec_ny.png",
+            "spec_pz.png",
+        ]);
+        assert_eq!(
+            vec![(
+                PathBuf::from("spec.png"),
+                paths(&[
+                    "spec_px.png",
+                    "spec_nx.png",
+                    "spec_py.png",
+                    "spec_ny.png",
+                    "spec_pz.png",
+                    "spec_nz.png",
+                ])
+            )],
+            group_layer_files(files)
+        );
+    }
+
+    #[test]
+    fn group_layer_files_six_numbered_layers() {
+        let files = paths(&[
+            "eye_5.png",
+            "eye_4.png",
+            "eye_3.png",
+            "eye_2.png",
+            "eye_1.png",
+            "eye_0.png",
+        ]);
+        assert_eq!(
+            vec![(
+                PathBuf::from("eye.png"),
+                paths(&[
+                    "eye_0.png",
+                    "eye_1.png",
+                    "eye_2.png",
+                    "eye_3.png",
+                    "eye_4.png",
+                    "eye_5.png",
+                ])
+            )],
+            group_layer_files(files)
+        );
+    }
+
+    #[test]
+    fn group_layer_files_preserves_order() {
+        let files = paths(&["a.png", "eye_1.png", "b_col.png", "eye_0.png"]);
+        assert_eq!(
+            vec![
+                (PathBuf::from("a.png"), Vec::new()),
+                (PathBuf::from("eye.png"), paths(&["eye_0.png", "eye_1.png"])),
+                (PathBuf::from("b_col.png"), Vec::new()),
+            ],
+            group_layer_files(files)
+        );
+    }
+
+    #[test]
+    fn group_layer_files_incomplete_layers() {
+        // Layers must be numbered 0..N without gaps or duplicates.
+        let files = paths(&["eye_0.png", "eye_2.png", "bg_1.png", "bg_px.png"]);
+        assert_eq!(
+            vec![
+                (PathBuf::from("eye_0.png"), Vec::new()),
+                (PathBuf::from("eye_2.png"), Vec::new()),
+                (PathBuf::from("bg_1.png"), Vec::new()),
+                (PathBuf::from("bg_px.png"), Vec::new()),
+            ],
+            group_layer_files(files)
+        );
+    }
+
+    #[test]
+    fn group_layer_files_single_layer() {
+        let files = paths(&["eye_0.png"]);
+        assert_eq!(
+            vec![(PathBuf::from("eye_0.png"), Vec::new())],
+            group_layer_files(files)
+        );
+    }
+
+    #[test]
+    fn group_layer_files_ignores_non_image_files() {
+        let files = paths(&[
+            "bg_0.nutexb",
+            "bg_1.nutexb",
+            "c_0.dds",
+            "c_1.dds",
+            "d_0.bntx",
+        ]);
+        assert_eq!(
+            vec![
+                (PathBuf::from("bg_0.nutexb"), Vec::new()),
+                (PathBuf::from("bg_1.nutexb"), Vec::new()),
+                (PathBuf::from("c_0.dds"), Vec::new()),
+                (PathBuf::from("c_1.dds"), Vec::new()),
+                (PathBuf::from("d_0.bntx"), Vec::new()),
+            ],
+            group_layer_files(files)
+        );
+    }
+
+    #[test]
+    fn group_layer_files_ignores_depth_slices() {
+        let files: Vec<_> = (0..2)
+            .map(|i| depth_slice_path(Path::new("lut.png"), i))
+            .collect();
+        assert_eq!(
+            vec![
+                (PathBuf::from("lut_z0.png"), Vec::new()),
+                (PathBuf::from("lut_z1.png"), Vec::new()),
+            ],
+            group_layer_files(files)
+        );
+    }
+}
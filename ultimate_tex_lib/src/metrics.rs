@@ -0,0 +1,285 @@
+use std::{fmt, path::Path};
+
+use image_dds::{
+    SurfaceRgba32Float,
+    image::{ImageFormat, Rgb, RgbImage},
+};
+
+use crate::{Error, ImageFile, save_to_file, write_dynamic_image};
+
+// The size of the windows used for calculating SSIM.
+const SSIM_WINDOW_SIZE: u32 = 8;
+
+/// Image quality for an encoded texture compared to the source image.
+/// Values are calculated from the base mipmap of each layer and depth slice.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityMetrics {
+    /// The peak signal-to-noise ratio in decibels for all channels.
+    /// Identical images have a value of infinity.
+    pub psnr: f64,
+    /// The mean structural similarity for all channels from `0.0` to `1.0`.
+    pub ssim: f64,
+    /// The largest absolute difference for each RGBA channel.
+    pub max_error: [f32; 4],
+}
+
+impl fmt::Display for QualityMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b, a] = self.max_error;
+        write!(
+            f,
+            "PSNR {:.2} dB, SSIM {:.4}, max error R {r:.4} G {g:.4} B {b:.4} A {a:.4}",
+            self.psnr, self.ssim
+        )
+    }
+}
+
+impl ImageFile {
+    /// Compare `encoded` to this image like after saving to a lossy format like BC7.
+    pub fn quality_metrics(&self, encoded: &ImageFile) -> Result<QualityMetrics, Error> {
+        let (source, encoded) = decode_for_comparison(self, encoded)?;
+        let slices = base_mipmap_slices(&source, &encoded);
+
+        let mut squared_error = 0.0;
+        let mut max_error = [0.0f32; 4];
+        for (a, b) in &slices {
+            for (i, (a, b)) in a.iter().zip(b.iter()).enumerate() {
+                let error = (a - b).abs();
+                squared_error += error as f64 * error as f64;
+                max_error[i % 4] = max_error[i % 4].max(error);
+            }
+        }
+        let value_count = slices.iter().map(|(a, _)| a.len()).sum::<usize>().max(1);
+        let mse = squared_error / value_count as f64;
+
+        // Use a peak value of 1.0 since values are normalized when decoding.
+        let psnr = if mse > 0.0 {
+            10.0 * (1.0 / mse).log10()
+        } else {
+            f64::INFINITY
+        };
+
+        let ssim_values: Vec<_> = slices
+            .iter()
+            .flat_map(|(a, b)| {
+                (0..4).flat_map(move |c| ssim_windows(a, b, source.width, source.height, c))
+            })
+            .collect();
+        let ssim = ssim_values.iter().sum::<f64>() / ssim_values.len().max(1) as f64;
+
+        Ok(QualityMetrics {
+            psnr,
+            ssim,
+            max_error,
+        })
+    }
+
+    /// Save an image showing the largest channel difference for each pixel
+    /// between `encoded` and the base mipmap of the first layer of this image.
+    ///
+    /// Differences are scaled relative to the largest difference to make small errors visible.
+    pub fn save_difference_heatmap(&self, encoded: &ImageFile, output: &Path) -> Result<(), Error> {
+        let (source, encoded) = decode_for_comparison(self, encoded)?;
+        let (a, b) = base_mipmap_slices(&source, &encoded)
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::decode("Failed to get data for layer 0"))?;
+
+        let errors: Vec<f32> = a
+            .chunks_exact(4)
+            .zip(b.chunks_exact(4))
+            .map(|(a, b)| {
+                a.iter()
+                    .zip(b)
+                    .map(|(a, b)| (a - b).abs())
+                    .fold(0.0, f32::max)
+            })
+            .collect();
+        let max_error = errors.iter().copied().fold(0.0, f32::max);
+
+        let mut heatmap = RgbImage::new(source.width, source.height);
+        for (pixel, error) in heatmap.pixels_mut().zip(errors) {
+            let t = if max_error > 0.0 {
+                error / max_error
+            } else {
+                0.0
+            };
+            *pixel = heatmap_color(t);
+        }
+
+        let format = ImageFormat::from_path(output)?;
+        save_to_file(output, |writer| {
+            write_dynamic_image(heatmap.into(), writer, format)
+        })
+    }
+}
+
+fn decode_for_comparison(
+    source: &ImageFile,
+    encoded: &ImageFile,
+) -> Result<(SurfaceRgba32Float<Vec<f32>>, SurfaceRgba32Float<Vec<f32>>), Error> {
+    let source = source.decode_rgbaf32()?;
+    let encoded = encoded.decode_rgbaf32()?;
+
+    let dimensions = |s: &SurfaceRgba32Float<Vec<f32>>| (s.width, s.height, s.depth, s.layers);
+    if dimensions(&encoded) != dimensions(&source) {
+        return Err(Error::CompareDimensions {
+            actual: dimensions(&encoded),
+            expected: dimensions(&source),
+        });
+    }
+    Ok((source, encoded))
+}
+
+fn base_mipmap_slices<'a>(
+    source: &'a SurfaceRgba32Float<Vec<f32>>,
+    encoded: &'a SurfaceRgba32Float<Vec<f32>>,
+) -> Vec<(&'a [f32], &'a [f32])> {
+    (0..source.layers)
+        .flat_map(|layer| (0..source.depth).map(move |depth| (layer, depth)))
+        .filter_map(|(layer, depth)| {
+            Some((source.get(layer, depth, 0)?, encoded.get(layer, depth, 0)?))
+        })
+        .collect()
+}
+
+// The SSIM for channel `c` of each window in the RGBA images `a` and `b`.
+fn ssim_windows(
+    a: &[f32],
+    b: &[f32],
+    width: u32,
+    height: u32,
+    c: usize,
+) -> impl Iterator<Item = f64> {
+    // Constants for a dynamic range of 1.0.
+    const C1: f64 = 0.01 * 0.01;
+    const C2: f64 = 0.03 * 0.03;
+
+    let windows = (0..height)
+        .step_by(SSIM_WINDOW_SIZE as usize)
+        .flat_map(move |y| {
+            (0..width)
+                .step_by(SSIM_WINDOW_SIZE as usize)
+                .map(move |x| (x, y))
+        });
+
+    windows.map(move |(x0, y0)| {
+        // Windows at the edges may be smaller for dimensions that aren't a multiple of the window size.
+        let values: Vec<(f64, f64)> = (y0..(y0 + SSIM_WINDOW_SIZE).min(height))
+            .flat_map(|y| (x0..(x0 + SSIM_WINDOW_SIZE).min(width)).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let i = (y * width + x) as usize * 4 + c;
+                (a[i] as f64, b[i] as f64)
+            })
+            .collect();
+        let n = values.len() as f64;
+
+        let mean_a = values.iter().map(|(a, _)| a).sum::<f64>() / n;
+        let mean_b = values.iter().map(|(_, b)| b).sum::<f64>() / n;
+        let variance_a = values
+            .iter()
+            .map(|(a, _)| (a - mean_a).powi(2))
+            .sum::<f64>()
+            / n;
+        let variance_b = values
+            .iter()
+            .map(|(_, b)| (b - mean_b).powi(2))
+            .sum::<f64>()
+            / n;
+        let covariance = values
+            .iter()
+            .map(|(a, b)| (a - mean_a) * (b - mean_b))
+            .sum::<f64>()
+            / n;
+
+        ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+            / ((mean_a * mean_a + mean_b * mean_b + C1) * (variance_a + variance_b + C2))
+    })
+}
+
+// Map values from 0.0 to 1.0 to black, red, yellow, and white.
+fn heatmap_color(t: f32) -> Rgb<u8> {
+    let to_u8 = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Rgb([to_u8(t * 3.0), to_u8(t * 3.0 - 1.0), to_u8(t * 3.0 - 2.0)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image_dds::SurfaceRgba8;
+
+    fn image(width: u32, height: u32, data: Vec<u8>) -> ImageFile {
+        ImageFile::Image(SurfaceRgba8 {
+            width,
+            height,
+            depth: 1,
+            layers: 1,
+            mipmaps: 1,
+            data,
+        })
+    }
+
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..width * height * 4).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn quality_metrics_identical() {
+        let a = image(4, 4, gradient(4, 4));
+        let metrics = a.quality_metrics(&image(4, 4, gradient(4, 4))).unwrap();
+        assert_eq!(f64::INFINITY, metrics.psnr);
+        assert!((metrics.ssim - 1.0).abs() < 1e-9);
+        assert_eq!([0.0; 4], metrics.max_error);
+    }
+
+    #[test]
+    fn quality_metrics_single_pixel() {
+        let a = image(4, 4, vec![0; 64]);
+        let mut data = vec![0; 64];
+        data[4 * 5 + 1] = 255;
+        let metrics = a.quality_metrics(&image(4, 4, data)).unwrap();
+
+        // A single value out of 64 with the maximum error.
+        assert!((metrics.psnr - 10.0 * 64f64.log10()).abs() < 1e-6);
+        assert!(metrics.ssim < 1.0);
+        assert_eq!([0.0, 1.0, 0.0, 0.0], metrics.max_error);
+    }
+
+    #[test]
+    fn quality_metrics_partial_windows() {
+        // 10x9 uses 8x8, 2x8, 8x1, and 2x1 windows.
+        let a = image(10, 9, gradient(10, 9));
+        let metrics = a.quality_metrics(&image(10, 9, gradient(10, 9))).unwrap();
+        assert!((metrics.ssim - 1.0).abs() < 1e-9);
+
+        // Differences in the last row and column should still be included.
+        let mut data = gradient(10, 9);
+        let last = data.len() - 4;
+        data[last] = data[last].wrapping_add(128);
+        let metrics = a.quality_metrics(&image(10, 9, data)).unwrap();
+        assert!(metrics.psnr.is_finite());
+        assert!(metrics.ssim < 1.0);
+    }
+
+    #[test]
+    fn ssim_windows_count() {
+        let data = vec![0.5; 10 * 9 * 4];
+        let values: Vec<_> = ssim_windows(&data, &data, 10, 9, 0).collect();
+        assert_eq!(4, values.len());
+        assert!(values.iter().all(|v| (v - 1.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn quality_metrics_dimensions() {
+        let a = image(4, 4, vec![0; 64]);
+        let result = a.quality_metrics(&image(2, 2, vec![0; 16]));
+        assert!(matches!(
+            result,
+            Err(Error::CompareDimensions {
+                actual: (2, 2, 1, 1),
+                expected: (4, 4, 1, 1)
+            })
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use crate::{Container, Error, NutexbFile, decode_rgbaf32, nutexb_to_dds};
+
+/// The result of optimizing the padding of a single nutexb file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimizedFile {
+    pub path: PathBuf,
+    pub original_size: u64,
+    pub optimized_size: u64,
+}
+
+impl OptimizedFile {
+    pub fn bytes_saved(&self) -> u64 {
+        self.original_size.saturating_sub(self.optimized_size)
+    }
+}
+
+/// The results of optimizing the padding of multiple nutexb files.
+#[derive(Debug, Default)]
+pub struct OptimizeReport {
+    pub files: Vec<OptimizedFile>,
+    /// Files that could not be optimized and were left unchanged.
+    pub errors: Vec<(PathBuf, Error)>,
+}
+
+impl OptimizeReport {
+    /// The total bytes saved for all optimized files.
+    pub fn bytes_saved(&self) -> u64 {
+        self.files.iter().map(OptimizedFile::bytes_saved).sum()
+    }
+}
+
+/// Find all nutexb files in `folder` and its subfolders.
+pub fn find_nutexb_files(folder: &Path) -> Vec<PathBuf> {
+    globwalk::GlobWalkerBuilder::from_patterns(folder, &["*.{nutexb}"])
+        .build()
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Remove unnecessary padding from the nutexb file at `path`.
+/// If `dry_run` is `true`, the sizes are calculated without modifying the file.
+///
+/// The file is only written if the decoded image data is identical after optimizing.
+pub fn optimize_nutexb_file(path: &Path, dry_run: bool) -> Result<OptimizedFile, Error> {
+    let original_size = std::fs::metadata(path)?.len();
+
+    let nutexb =
+        NutexbFile::read_from_file(path).map_err(Error::invalid_container(Container::Nutexb))?;
+    let bytes = optimize_nutexb(nutexb)?;
+
+    if !dry_run && bytes.len() as u64 != original_size {
+        std::fs::write(path, &bytes)?;
+    }
+
+    Ok(OptimizedFile {
+        path: path.to_path_buf(),
+        original_size,
+        optimized_size: bytes.len() as u64,
+    })
+}
+
+// Remove the padding without changing the mipmap count or other footer values.
+fn optimize_nutexb(mut nutexb: NutexbFile) -> Result<Vec<u8>, Error> {
+    // Compare with the file as it was read to catch any changes to the mipmaps.
+    let original = decode_rgbaf32(&nutexb_to_dds(&nutexb)?)?;
+    let mipmap_count = nutexb.footer.mipmap_count;
+
+    nutexb.optimize_size();
+    let mut writer = Cursor::new(Vec::new());
+    nutexb.write(&mut writer).map_err(Error::write)?;
+    let bytes = writer.into_inner();
+
+    // Read the written bytes to check the file that would actually be saved.
+    let optimized = NutexbFile::read(&mut Cursor::new(&bytes))
+        .map_err(Error::invalid_container(Container::Nutexb))?;
+    if optimized.footer.mipmap_count != mipmap_count
+        || decode_rgbaf32(&nutexb_to_dds(&optimized)?)?.data != original.data
+    {
+        return Err(Error::OptimizedDataMismatch);
+    }
+
+    Ok(bytes)
+}
+
+/// Remove unnecessary padding from each nutexb file in `paths` and collect the results.
+/// Files with errors are left unchanged and don't prevent optimizing the remaining files.
+pub fn optimize_nutexb_files(paths: &[PathBuf], dry_run: bool) -> OptimizeReport {
+    let mut report = OptimizeReport::default();
+    for path in paths {
+        match optimize_nutexb_file(path, dry_run) {
+            Ok(file) => report.files.push(file),
+            Err(e) => report.errors.push((path.clone(), e)),
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image_dds::{ImageFormat, Surface};
+
+    #[test]
+    fn optimize_nutexb_keeps_full_mipmap_chain() {
+        // 4x4, 2x2, and 1x1 mipmaps.
+        let dds = Surface {
+            width: 4,
+            height: 4,
+            depth: 1,
+            layers: 1,
+            mipmaps: 3,
+            image_format: ImageFormat::Rgba8Unorm,
+            data: (0..84).map(|i| i as u8).collect::<Vec<_>>(),
+        }
+        .to_dds()
+        .unwrap();
+        let nutexb = NutexbFile::from_dds(&dds, "def_mario_001_col".to_string()).unwrap();
+        let original = decode_rgbaf32(&nutexb_to_dds(&nutexb).unwrap()).unwrap();
+
+        let bytes = optimize_nutexb(nutexb).unwrap();
+
+        let optimized = NutexbFile::read(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(3, optimized.footer.mipmap_count);
+        let decoded = decode_rgbaf32(&nutexb_to_dds(&optimized).unwrap()).unwrap();
+        assert_eq!(3, decoded.mipmaps);
+        assert_eq!(original.data, decoded.data);
+    }
+}
@@ -0,0 +1,224 @@
+use std::path::Path;
+
+use image_dds::{ImageFormat, Mipmaps, Quality};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, ImageFileType, file_name_no_extension};
+
+/// The output settings for converting a file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputSettings {
+    pub file_type: ImageFileType,
+    pub format: ImageFormat,
+    pub quality: Quality,
+    pub mipmaps: Mipmaps,
+}
+
+/// Whether a format stores color data like albedo or non color data like normals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, strum::Display)]
+pub enum ColorSpace {
+    /// Use the sRGB variant of the format if available.
+    #[strum(to_string = "sRGB")]
+    Srgb,
+    /// Use the linear variant of the format if available.
+    #[strum(to_string = "linear")]
+    Linear,
+}
+
+/// Output settings for files with names matching a pattern.
+/// Settings that are `None` keep their existing values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PresetRule {
+    /// A pattern for the file name without the extension like `*_col`.
+    /// `*` matches any number of characters, and matching ignores case.
+    pub pattern: String,
+    #[serde(default)]
+    pub file_type: Option<ImageFileType>,
+    #[serde(default)]
+    pub format: Option<ImageFormat>,
+    /// Convert the format to sRGB or linear after applying [PresetRule::format].
+    #[serde(default)]
+    pub color_space: Option<ColorSpace>,
+    #[serde(default)]
+    pub quality: Option<Quality>,
+    #[serde(default)]
+    pub mipmaps: Option<Mipmaps>,
+}
+
+impl PresetRule {
+    fn color_space(pattern: &str, color_space: ColorSpace) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            file_type: None,
+            format: None,
+            color_space: Some(color_space),
+            quality: None,
+            mipmaps: None,
+        }
+    }
+
+    /// Returns `true` if the file name of `path` without the extension matches the pattern.
+    pub fn matches(&self, path: &Path) -> bool {
+        let name = file_name_no_extension(path).to_lowercase();
+        matches_pattern(&self.pattern.to_lowercase(), &name)
+    }
+
+    fn apply(&self, settings: &mut OutputSettings) {
+        if let Some(file_type) = self.file_type {
+            settings.file_type = file_type;
+        }
+        if let Some(format) = self.format {
+            settings.format = format;
+        }
+        if let Some(color_space) = self.color_space {
+            settings.format = with_color_space(settings.format, color_space);
+        }
+        if let Some(quality) = self.quality {
+            settings.quality = quality;
+        }
+        if let Some(mipmaps) = self.mipmaps {
+            settings.mipmaps = mipmaps;
+        }
+    }
+}
+
+/// Rules for choosing output settings based on the file name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Presets {
+    /// Only the first matching rule is applied.
+    pub rules: Vec<PresetRule>,
+}
+
+impl Default for Presets {
+    /// Rules for the texture name suffixes used by Smash Ultimate.
+    fn default() -> Self {
+        Self {
+            rules: vec![
+                // Color textures.
+                PresetRule::color_space("*_col", ColorSpace::Srgb),
+                PresetRule::color_space("*_emi", ColorSpace::Srgb),
+                // Non color textures like normals or packed material parameters.
+                PresetRule::color_space("*_nor", ColorSpace::Linear),
+                PresetRule::color_space("*_prm", ColorSpace::Linear),
+                PresetRule::color_space("*_gao", ColorSpace::Linear),
+            ],
+        }
+    }
+}
+
+impl Presets {
+    /// Load rules from a JSON file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Save rules to a JSON file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// The first rule matching the file name of `path`.
+    pub fn find_rule(&self, path: &Path) -> Option<&PresetRule> {
+        self.rules.iter().find(|r| r.matches(path))
+    }
+
+    /// Apply the first rule matching the file name of `path` to `settings`.
+    pub fn apply(&self, path: &Path, mut settings: OutputSettings) -> OutputSettings {
+        if let Some(rule) = self.find_rule(path) {
+            rule.apply(&mut settings);
+        }
+        settings
+    }
+}
+
+/// The sRGB or linear variant of `format` or `format` if there is no variant.
+pub fn with_color_space(format: ImageFormat, color_space: ColorSpace) -> ImageFormat {
+    match srgb_variants(format) {
+        Some((linear, srgb)) => match color_space {
+            ColorSpace::Srgb => srgb,
+            ColorSpace::Linear => linear,
+        },
+        None => format,
+    }
+}
+
+/// [ColorSpace::Srgb] for sRGB formats like BC7RgbaUnormSrgb and [ColorSpace::Linear] otherwise.
+pub fn color_space(format: ImageFormat) -> ColorSpace {
+    match srgb_variants(format) {
+        Some((_, srgb)) if srgb == format => ColorSpace::Srgb,
+        _ => ColorSpace::Linear,
+    }
+}
+
+// The linear and sRGB variants for formats that support both.
+fn srgb_variants(format: ImageFormat) -> Option<(ImageFormat, ImageFormat)> {
+    [
+        (ImageFormat::Rgba8Unorm, ImageFormat::Rgba8UnormSrgb),
+        (ImageFormat::Bgra8Unorm, ImageFormat::Bgra8UnormSrgb),
+        (ImageFormat::BC1RgbaUnorm, ImageFormat::BC1RgbaUnormSrgb),
+        (ImageFormat::BC2RgbaUnorm, ImageFormat::BC2RgbaUnormSrgb),
+        (ImageFormat::BC3RgbaUnorm, ImageFormat::BC3RgbaUnormSrgb),
+        (ImageFormat::BC7RgbaUnorm, ImageFormat::BC7RgbaUnormSrgb),
+    ]
+    .into_iter()
+    .find(|(linear, srgb)| format == *linear || format == *srgb)
+}
+
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+            // Try every possible length for the wildcard.
+            (0..=name.len())
+                .filter(|i| name.is_char_boundary(*i))
+                .any(|i| matches_pattern(rest, &name[i..]))
+        }
+        None => pattern == name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_pattern_exact() {
+        assert!(matches_pattern("def_mario_001_col", "def_mario_001_col"));
+        assert!(!matches_pattern("def_mario_001_col", "def_mario_001_nor"));
+    }
+
+    #[test]
+    fn matches_pattern_wildcard() {
+        assert!(matches_pattern("*_col", "def_mario_001_col"));
+        assert!(matches_pattern("*_col", "_col"));
+        assert!(!matches_pattern("*_col", "def_mario_001_col_2"));
+        assert!(matches_pattern("def_*", "def_mario_001_col"));
+        assert!(matches_pattern("*", ""));
+    }
+
+    #[test]
+    fn matches_pattern_multiple_wildcards() {
+        assert!(matches_pattern("*mario*_col", "def_mario_001_col"));
+        assert!(matches_pattern("a*b*c", "abc"));
+        assert!(matches_pattern("a*b*c", "aXbYbZc"));
+        assert!(!matches_pattern("a*b*c", "aXcYb"));
+    }
+
+    #[test]
+    fn matches_pattern_non_ascii() {
+        assert!(matches_pattern("*é_col", "café_col"));
+        assert!(!matches_pattern("*é_col", "cafe_col"));
+    }
+
+    #[test]
+    fn preset_rule_matches_ignores_case() {
+        let rule = PresetRule::color_space("*_COL", ColorSpace::Srgb);
+        assert!(rule.matches(Path::new("fighter/Mario_Col.png")));
+        assert!(!rule.matches(Path::new("fighter/mario_nor.png")));
+    }
+}
@@ -0,0 +1,255 @@
+use std::path::Path;
+
+use image_dds::ImageFormat;
+
+use crate::{
+    ColorSpace, Container, Error, ImageFile, NutexbFile, Presets, file_name_no_extension,
+    full_mipmap_count, internal_name_matches_file_name, with_color_space,
+};
+
+/// The largest width, height, or depth that doesn't produce a warning.
+/// Larger textures are rarely used in game and waste memory.
+pub const MAX_DIMENSION: u32 = 4096;
+
+/// The properties of a texture to check with [validate].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureProperties<'a> {
+    pub container: Container,
+    pub format: ImageFormat,
+    /// The width, height, and depth.
+    pub dimensions: (u32, u32, u32),
+    /// The number of mipmaps or `None` to skip checking the mipmap count.
+    pub mipmap_count: Option<u32>,
+    /// The name stored in the file for nutexb or bntx files.
+    pub internal_name: Option<&'a str>,
+}
+
+/// A potential problem with a texture in game.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ValidationIssue {
+    #[error("{format} is not supported for {container} files")]
+    UnsupportedFormat {
+        container: Container,
+        format: ImageFormat,
+    },
+
+    #[error("dimensions {}x{}x{} are not powers of two", .dimensions.0, .dimensions.1, .dimensions.2)]
+    NonPowerOfTwo { dimensions: (u32, u32, u32) },
+
+    #[error(
+        "dimensions {}x{}x{} are larger than {max}",
+        .dimensions.0,
+        .dimensions.1,
+        .dimensions.2,
+        max = MAX_DIMENSION
+    )]
+    Oversized { dimensions: (u32, u32, u32) },
+
+    #[error("mipmap count {count} is larger than the maximum of {max} and will be clamped")]
+    MipmapCount { count: u32, max: u32 },
+
+    #[error("{format} should be {expected} based on the file name")]
+    ColorSpace {
+        format: ImageFormat,
+        expected: ColorSpace,
+    },
+
+    #[error("internal name {name:?} does not match the file name {file_name:?}")]
+    InternalName { name: String, file_name: String },
+}
+
+/// Check a texture saved to `path` for problems in game.
+///
+/// The `color_space` is the expected color space for the file's role like [PresetRule::color_space](crate::PresetRule::color_space).
+pub fn validate(
+    path: &Path,
+    texture: &TextureProperties,
+    color_space: Option<ColorSpace>,
+) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    // Image formats like PNG are only used for editing.
+    let is_game_container = matches!(texture.container, Container::Nutexb | Container::Bntx);
+
+    if is_game_container && !is_supported_format(texture.container, texture.format) {
+        issues.push(ValidationIssue::UnsupportedFormat {
+            container: texture.container,
+            format: texture.format,
+        });
+    }
+
+    let (width, height, depth) = texture.dimensions;
+    if is_game_container
+        && (!width.is_power_of_two()
+            || !height.is_power_of_two()
+            || (depth > 1 && !depth.is_power_of_two()))
+    {
+        issues.push(ValidationIssue::NonPowerOfTwo {
+            dimensions: texture.dimensions,
+        });
+    }
+    if is_game_container && width.max(height).max(depth) > MAX_DIMENSION {
+        issues.push(ValidationIssue::Oversized {
+            dimensions: texture.dimensions,
+        });
+    }
+
+    if let Some(count) = texture.mipmap_count {
+        let max = full_mipmap_count(width, height, depth);
+        if count > max {
+            issues.push(ValidationIssue::MipmapCount { count, max });
+        }
+    }
+
+    if let Some(expected) = color_space
+        && texture.container != Container::Image
+        && with_color_space(texture.format, expected) != texture.format
+    {
+        issues.push(ValidationIssue::ColorSpace {
+            format: texture.format,
+            expected,
+        });
+    }
+
+    if let Some(name) = texture.internal_name
+        && !internal_name_matches_file_name(name, path)
+    {
+        issues.push(ValidationIssue::InternalName {
+            name: name.to_string(),
+            file_name: file_name_no_extension(path),
+        });
+    }
+
+    issues
+}
+
+/// Check an existing texture file for problems in game
+/// using `presets` for the expected color space.
+pub fn validate_file(path: &Path, presets: &Presets) -> Result<Vec<ValidationIssue>, Error> {
+    let image = ImageFile::from_file(path)?;
+    let mipmap_count = match &image {
+        // Loading fixes invalid mipmap counts, so check the original value.
+        // Only nutexb files store a mipmap count that gets clamped when loading.
+        ImageFile::Nutexb(_) => {
+            let nutexb = NutexbFile::read_from_file(path)
+                .map_err(Error::invalid_container(Container::Nutexb))?;
+            Some(nutexb.footer.mipmap_count)
+        }
+        _ => None,
+    };
+
+    let internal_name = image.internal_name();
+    let texture = TextureProperties {
+        container: image.container(),
+        format: image.image_format()?,
+        dimensions: image.dimensions(),
+        mipmap_count,
+        internal_name: internal_name.as_deref(),
+    };
+    let color_space = presets.find_rule(path).and_then(|r| r.color_space);
+    Ok(validate(path, &texture, color_space))
+}
+
+fn is_supported_format(container: Container, format: ImageFormat) -> bool {
+    match container {
+        // Formats with a corresponding nutexb format.
+        Container::Nutexb => matches!(
+            format,
+            ImageFormat::R8Unorm
+                | ImageFormat::Rgba8Unorm
+                | ImageFormat::Rgba8UnormSrgb
+                | ImageFormat::Rgba32Float
+                | ImageFormat::Bgra8Unorm
+                | ImageFormat::Bgra8UnormSrgb
+                | ImageFormat::BC1RgbaUnorm
+                | ImageFormat::BC1RgbaUnormSrgb
+                | ImageFormat::BC2RgbaUnorm
+                | ImageFormat::BC2RgbaUnormSrgb
+                | ImageFormat::BC3RgbaUnorm
+                | ImageFormat::BC3RgbaUnormSrgb
+                | ImageFormat::BC4RUnorm
+                | ImageFormat::BC4RSnorm
+                | ImageFormat::BC5RgUnorm
+                | ImageFormat::BC5RgSnorm
+                | ImageFormat::BC6hRgbUfloat
+                | ImageFormat::BC6hRgbSfloat
+                | ImageFormat::BC7RgbaUnorm
+                | ImageFormat::BC7RgbaUnormSrgb
+        ),
+        // UI textures use 8-bit or block compressed formats.
+        Container::Bntx => matches!(
+            format,
+            ImageFormat::R8Unorm
+                | ImageFormat::Rgba8Unorm
+                | ImageFormat::Rgba8UnormSrgb
+                | ImageFormat::Bgra8Unorm
+                | ImageFormat::Bgra8UnormSrgb
+                | ImageFormat::BC1RgbaUnorm
+                | ImageFormat::BC1RgbaUnormSrgb
+                | ImageFormat::BC2RgbaUnorm
+                | ImageFormat::BC2RgbaUnormSrgb
+                | ImageFormat::BC3RgbaUnorm
+                | ImageFormat::BC3RgbaUnormSrgb
+                | ImageFormat::BC4RUnorm
+                | ImageFormat::BC5RgUnorm
+                | ImageFormat::BC7RgbaUnorm
+                | ImageFormat::BC7RgbaUnormSrgb
+        ),
+        Container::Dds | Container::Image => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(container: Container) -> TextureProperties<'static> {
+        TextureProperties {
+            container,
+            format: ImageFormat::BC7RgbaUnorm,
+            dimensions: (8192, 100, 1),
+            mipmap_count: None,
+            internal_name: None,
+        }
+    }
+
+    #[test]
+    fn validate_dimensions_nutexb() {
+        assert_eq!(
+            vec![
+                ValidationIssue::NonPowerOfTwo {
+                    dimensions: (8192, 100, 1)
+                },
+                ValidationIssue::Oversized {
+                    dimensions: (8192, 100, 1)
+                },
+            ],
+            validate(Path::new("a.nutexb"), &properties(Container::Nutexb), None)
+        );
+    }
+
+    #[test]
+    fn validate_mipmap_count() {
+        let texture = TextureProperties {
+            dimensions: (256, 256, 1),
+            mipmap_count: Some(9),
+            ..properties(Container::Nutexb)
+        };
+        assert!(validate(Path::new("a.nutexb"), &texture, None).is_empty());
+
+        let texture = TextureProperties {
+            mipmap_count: Some(10),
+            ..texture
+        };
+        assert_eq!(
+            vec![ValidationIssue::MipmapCount { count: 10, max: 9 }],
+            validate(Path::new("a.nutexb"), &texture, None)
+        );
+    }
+
+    #[test]
+    fn validate_dimensions_image() {
+        // Images like PNG are only used for editing.
+        assert!(validate(Path::new("a.png"), &properties(Container::Image), None).is_empty());
+    }
+}